@@ -1,19 +1,35 @@
 use crate::error::ContractError;
-use crate::helpers::get_winner;
+use crate::helpers::{
+    break_tie, casting_vote_deadline, classic_options, current_status, eligible_weight, get_winner,
+    instant_runoff, is_classic, passing_outcome, voting_power,
+};
 use crate::msg::{
-    ExecuteMsg, InstantiateMsg, QueryMsg, VBCountResponse, VBOCResponse, VoteBoxListResponse,
+    BallotListResponse, BallotResponse, ClaimStatusResponse, CreditsResponse, DelegatorsResponse,
+    EligibleVotersResponse, ExecuteMsg, InstantiateMsg, MerkleProof, MigrateMsg, QueryMsg,
+    ReceiveMsg, ResultResponse, RunoffRound, VBCountResponse, VBOCResponse, VoteBoxListResponse,
     VoteResponse, VoteboxStatistics,
 };
-use crate::state::{Vote, VOTE_BOX_LIST, VOTE_BOX_SEQ};
+use crate::state::{
+    Ballot, Commitment, DelegatedVote, FinalResult, Outcome, Status, Thresholds, TieBreak, Vote,
+    VoteKind, VoteOption, VotingPower, ADMIN, BALLOTS, CLAIMS, CLASSIC_OPTIONS, COMMITMENTS,
+    COUNTED_DELEGATORS, DELEGATED_BOXES, DELEGATED_VOTES, DELEGATIONS, DELEGATORS, ELIGIBLE_VOTERS,
+    GLOBAL_DELEGATION, LOCKED_TOKENS, PENDING_VOTERS, VOTERS, VOTE_BOX_LIST, VOTE_BOX_SEQ,
+};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Order, Response,
-    StdError, StdResult, Uint128, Uint64,
+    from_binary, to_binary, to_vec, Addr, BankMsg, Binary, BlockInfo, Coin, CosmosMsg, Decimal,
+    Deps, DepsMut, Empty, Env, Event, MessageInfo, Order, Response, StdError, StdResult, Storage,
+    Uint128, Uint64, WasmMsg,
 };
 use cw2::set_contract_version;
-use cw_storage_plus::Bound;
-use cw_utils::Scheduled;
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
+use cw_storage_plus::{Bound, Map};
+use cw_utils::{Duration, Scheduled};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
 use std::ops::Add;
 
 // version info for migration info
@@ -26,14 +42,102 @@ pub fn instantiate(
     deps: DepsMut,
     _env: Env,
     _info: MessageInfo,
-    _msg: InstantiateMsg,
+    msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     VOTE_BOX_SEQ.save(deps.storage, &Uint64::zero());
+    if let Some(admin) = msg.admin {
+        ADMIN.save(deps.storage, &deps.api.addr_validate(&admin)?)?;
+    }
 
     Ok(Response::new().add_attribute("method", "instantiate"))
 }
 
+/// Voter list and fixed option tallies as stored inline in `Vote` by contract versions
+/// before 0.2
+#[derive(Serialize, Deserialize)]
+struct LegacyVoters {
+    #[serde(default)]
+    voters: Vec<Addr>,
+    yes_count: Option<Uint128>,
+    no_count: Option<Uint128>,
+    abstain_count: Option<Uint128>,
+    no_with_veto_count: Option<Uint128>,
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    if let Some(admin) = msg.admin {
+        ADMIN.save(deps.storage, &deps.api.addr_validate(&admin)?)?;
+    }
+    let legacy_list: Map<u64, LegacyVoters> = Map::new("votebox list");
+    let ids: StdResult<Vec<_>> = VOTE_BOX_LIST
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect();
+
+    // writing one index entry per voter does not fit a transaction on popular voteboxes,
+    // so the voter lists are parked and moved over in batches by `migrate_voters`
+    let mut pending_voters = 0u64;
+    for id in ids? {
+        let legacy = legacy_list.load(deps.storage, id)?;
+        if !legacy.voters.is_empty() {
+            pending_voters += legacy.voters.len() as u64;
+            PENDING_VOTERS.save(deps.storage, id, &legacy.voters)?;
+        }
+        // saving again drops the inline voter list and tallies from the stored box
+        let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id)?;
+        if vote_box.options.is_empty() {
+            let counts = [
+                legacy.no_count,
+                legacy.abstain_count,
+                legacy.yes_count,
+                legacy.no_with_veto_count,
+            ];
+            vote_box.options = classic_options();
+            for (option, count) in vote_box.options.iter_mut().zip(counts.iter()) {
+                option.count = count.unwrap_or_default();
+            }
+        }
+        VOTE_BOX_LIST.save(deps.storage, id, &vote_box)?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("pending_voters", pending_voters.to_string()))
+}
+
+const DEFAULT_MIGRATE_LIMIT: u32 = 100;
+const MAX_MIGRATE_LIMIT: u32 = 500;
+
+pub fn execute_migrate_voters(
+    deps: DepsMut,
+    id: Uint64,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let mut pending = PENDING_VOTERS
+        .may_load(deps.storage, id.u64())?
+        .ok_or(ContractError::NothingToMigrate {})?;
+    let limit = limit
+        .unwrap_or(DEFAULT_MIGRATE_LIMIT)
+        .min(MAX_MIGRATE_LIMIT) as usize;
+
+    let batch = pending.split_off(pending.len().saturating_sub(limit));
+    for voter in &batch {
+        VOTERS.save(deps.storage, (id.u64(), voter), &Empty {})?;
+    }
+    if pending.is_empty() {
+        PENDING_VOTERS.remove(deps.storage, id.u64());
+    } else {
+        PENDING_VOTERS.save(deps.storage, id.u64(), &pending)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate_voters")
+        .add_attribute("migrated", batch.len().to_string())
+        .add_attribute("remaining", pending.len().to_string()))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -49,6 +153,20 @@ pub fn execute(
             description,
             create_date,
             native_denom,
+            cw20_addr,
+            voting_power,
+            allowlist,
+            options,
+            kind,
+            reveal_deadline,
+            hide_tally_until_end,
+            thresholds,
+            total_weight,
+            tie_break,
+            draft,
+            msgs,
+            timelock,
+            guardian,
         } => create_vote_box(
             deps,
             env,
@@ -59,185 +177,1558 @@ pub fn execute(
             description,
             create_date,
             native_denom,
+            cw20_addr,
+            voting_power,
+            allowlist,
+            options,
+            kind,
+            reveal_deadline,
+            hide_tally_until_end.unwrap_or(false),
+            thresholds,
+            total_weight,
+            tie_break.unwrap_or_default(),
+            draft.unwrap_or(false),
+            msgs.unwrap_or_default(),
+            timelock,
+            guardian,
         ),
-        ExecuteMsg::vote { id, vote_type } => execute_vote(deps, env, info, id, vote_type),
+        ExecuteMsg::vote {
+            id,
+            vote_type,
+            proof,
+            memo,
+            votes,
+        } => execute_vote(deps, env, info, id, vote_type, proof, memo, votes),
+        ExecuteMsg::vote_ranked {
+            id,
+            ranking,
+            proof,
+            memo,
+        } => execute_vote_ranked(deps, env, info, id, ranking, proof, memo),
+        ExecuteMsg::vote_approval {
+            id,
+            options,
+            proof,
+            memo,
+        } => execute_vote_approval(deps, env, info, id, options, proof, memo),
+        ExecuteMsg::commit_vote {
+            id,
+            commitment,
+            proof,
+            memo,
+        } => execute_commit_vote(deps, env, info, id, commitment, proof, memo),
+        ExecuteMsg::reveal_vote {
+            id,
+            vote_type,
+            salt,
+        } => execute_reveal_vote(deps, env, info, id, vote_type, salt),
+        ExecuteMsg::change_vote { id, vote_type } => {
+            execute_change_vote(deps, env, info, id, vote_type)
+        }
+        ExecuteMsg::retract_vote { id } => execute_retract_vote(deps, env, info, id),
+        ExecuteMsg::vote_open { id } => open_votebox(deps, env, info, id),
+        ExecuteMsg::vote_cancel { id } => cancel_votebox(deps, env, info, id),
+        ExecuteMsg::finalize { id } => execute_finalize(deps, env, id),
+        ExecuteMsg::execute_proposal { id } => execute_proposal(deps, env, id),
+        ExecuteMsg::veto_execution { id } => execute_veto(deps, env, info, id),
         ExecuteMsg::vote_reset { id } => reset(deps, env, info, id),
         ExecuteMsg::vote_remove { id } => remove_votebox(deps, env, info, id),
+        ExecuteMsg::deposit { id } => execute_deposit_native(deps, env, info, id),
+        ExecuteMsg::claim { id } => execute_claim(deps, env, info, id),
+        ExecuteMsg::receive(msg) => execute_receive(deps, env, info, msg),
+        ExecuteMsg::unlock { id } => execute_unlock(deps, env, info, id),
+        ExecuteMsg::migrate_voters { id, limit } => execute_migrate_voters(deps, id, limit),
+        ExecuteMsg::cast_deciding_vote { id, option } => {
+            execute_cast_deciding_vote(deps, env, info, id, option)
+        }
+        ExecuteMsg::delegate { id, delegate_to } => {
+            execute_delegate(deps, env, info, id, delegate_to)
+        }
+        ExecuteMsg::undelegate { id } => execute_undelegate(deps, info, id),
+        ExecuteMsg::update_eligible_voters { id, add, remove } => {
+            execute_update_eligible_voters(deps, env, info, id, add, remove)
+        }
     }
 }
-#[allow(unused_must_use)]
+const MAX_MEMO_LENGTH: usize = 256;
+
+#[allow(unused_must_use, clippy::too_many_arguments)]
 pub fn execute_vote(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     id: Uint64,
     vote_type: i32,
+    proof: Option<MerkleProof>,
+    memo: Option<String>,
+    votes: Option<Uint128>,
 ) -> Result<Response, ContractError> {
     let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
-    if vote_box.deadline.is_triggered(&env.block) {
-        return Err(ContractError::Expired {});
+    match vote_box.kind {
+        VoteKind::Single {} if vote_box.reveal_deadline.is_none() => {}
+        VoteKind::Quadratic { credits } => {
+            let votes = votes.unwrap_or_else(|| Uint128::new(1));
+            return execute_vote_quadratic(
+                deps, env, info, vote_box, credits, vote_type, votes, proof, memo,
+            );
+        }
+        _ => return Err(ContractError::WrongVoteKind {}),
+    }
+    let own_weight = voter_weight(deps.as_ref(), &env, &info, &vote_box, proof, &memo)?;
+
+    // voting directly takes the sender's weight back from the delegate that voted for it
+    release_delegated_vote(deps.storage, &env.block, &mut vote_box, &info.sender)?;
+    // delegators' tokens can't be pulled in, so locking voteboxes only count own balances
+    let delegated = match vote_box.voting_power {
+        VotingPower::Cw20Balance { lock: true, .. } => vec![],
+        _ => delegated_votes(deps.as_ref(), &vote_box, &info.sender)?,
+    };
+    let mut weight = own_weight;
+    for (delegator, delegated_weight) in delegated {
+        release_delegated_vote(deps.storage, &env.block, &mut vote_box, &delegator)?;
+        DELEGATED_VOTES.save(
+            deps.storage,
+            (id.u64(), &delegator),
+            &DelegatedVote {
+                delegate: info.sender.clone(),
+                weight: delegated_weight,
+            },
+        )?;
+        COUNTED_DELEGATORS.save(
+            deps.storage,
+            (id.u64(), &info.sender, &delegator),
+            &Empty {},
+        )?;
+        weight = weight.checked_add(delegated_weight)?;
+    }
+
+    let option = vote_option(&mut vote_box, vote_type, &env.block)?;
+    option.count = option.count.checked_add(weight)?;
+    let (label, count) = (option.label.clone(), option.count);
+
+    let ballot = Ballot {
+        vote_type,
+        ranking: vec![],
+        approvals: vec![],
+        allocation: vec![],
+        weight,
+        height: env.block.height,
+        time: env.block.time,
+        memo,
+    };
+    let mut res = record_ballot(deps, &env, &info, &mut vote_box, ballot)?
+        .add_attribute("method", "vote given")
+        .add_attribute("weight", weight)
+        .add_attribute("delegated_weight", weight - own_weight)
+        .add_attribute("option", label);
+    if !vote_box.hide_tally_until_end {
+        res = res.add_attribute("count", count);
+    }
+    Ok(res)
+}
+
+pub fn execute_vote_ranked(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
+    ranking: Vec<u32>,
+    proof: Option<MerkleProof>,
+    memo: Option<String>,
+) -> Result<Response, ContractError> {
+    let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    if vote_box.kind != (VoteKind::RankedChoice {}) {
+        return Err(ContractError::WrongVoteKind {});
+    }
+    let weight = voter_weight(deps.as_ref(), &env, &info, &vote_box, proof, &memo)?;
+
+    let options = vote_box.options.len();
+    for (i, option) in ranking.iter().enumerate() {
+        if *option as usize >= options || ranking[..i].contains(option) {
+            return Err(ContractError::InvalidRanking {});
+        }
+    }
+    // option tallies of a ranked box count first preferences
+    let vote_type = *ranking.first().ok_or(ContractError::InvalidRanking {})? as i32;
+    let option = vote_option(&mut vote_box, vote_type, &env.block)?;
+    option.count = option.count.checked_add(weight)?;
+
+    let ballot = Ballot {
+        vote_type,
+        ranking,
+        approvals: vec![],
+        allocation: vec![],
+        weight,
+        height: env.block.height,
+        time: env.block.time,
+        memo,
+    };
+    let res = record_ballot(deps, &env, &info, &mut vote_box, ballot)?;
+
+    Ok(res
+        .add_attribute("method", "ranked vote given")
+        .add_attribute("weight", weight)
+        .add_attribute("first_preference", vote_type.to_string()))
+}
+
+pub fn execute_vote_approval(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
+    options: Vec<u32>,
+    proof: Option<MerkleProof>,
+    memo: Option<String>,
+) -> Result<Response, ContractError> {
+    let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    if vote_box.kind != (VoteKind::Approval {}) {
+        return Err(ContractError::WrongVoteKind {});
+    }
+    let weight = voter_weight(deps.as_ref(), &env, &info, &vote_box, proof, &memo)?;
+
+    if options.is_empty() {
+        return Err(ContractError::EmptyApproval {});
     }
-    if vote_box.voters.contains(&info.sender) {
+    for (i, option) in options.iter().enumerate() {
+        if options[..i].contains(option) {
+            return Err(ContractError::DuplicateApproval {});
+        }
+        let option = vote_option(&mut vote_box, *option as i32, &env.block)?;
+        option.count = option.count.checked_add(weight)?;
+    }
+
+    let ballot = Ballot {
+        vote_type: options[0] as i32,
+        ranking: vec![],
+        approvals: options,
+        allocation: vec![],
+        weight,
+        height: env.block.height,
+        time: env.block.time,
+        memo,
+    };
+    let approved = ballot.approvals.len();
+    let res = record_ballot(deps, &env, &info, &mut vote_box, ballot)?;
+
+    Ok(res
+        .add_attribute("method", "approval vote given")
+        .add_attribute("weight", weight)
+        .add_attribute("approved", approved.to_string()))
+}
+
+/// Buys `votes` more votes for one option, paying the difference in quadratic cost from
+/// the voter's credit budget. The first ballot also registers the voter.
+#[allow(clippy::too_many_arguments)]
+fn execute_vote_quadratic(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    mut vote_box: Vote,
+    credits: Uint128,
+    vote_type: i32,
+    votes: Uint128,
+    proof: Option<MerkleProof>,
+    memo: Option<String>,
+) -> Result<Response, ContractError> {
+    let id = vote_box.id.u64();
+    let existing = BALLOTS.may_load(deps.storage, (id, &info.sender))?;
+    let mut ballot = match existing {
+        Some(ballot) => {
+            check_ballot(&env, &vote_box, &memo)?;
+            ballot
+        }
+        None => Ballot {
+            vote_type,
+            ranking: vec![],
+            approvals: vec![],
+            allocation: vec![Uint128::zero(); vote_box.options.len()],
+            weight: voter_weight(deps.as_ref(), &env, &info, &vote_box, proof, &memo)?,
+            height: env.block.height,
+            time: env.block.time,
+            memo: None,
+        },
+    };
+    if votes.is_zero() {
+        return Err(ContractError::InvalidVote {});
+    }
+
+    let option = vote_option(&mut vote_box, vote_type, &env.block)?;
+    option.count = option.count.checked_add(votes)?;
+
+    let already_spent = spent_credits(&ballot)?;
+    let index = vote_type as usize;
+    ballot.allocation[index] = ballot.allocation[index].checked_add(votes)?;
+    let spent = spent_credits(&ballot)?;
+    if spent > credits {
+        return Err(ContractError::InsufficientCredits {
+            remaining: credits - already_spent,
+        });
+    }
+
+    ballot.vote_type = vote_type;
+    ballot.height = env.block.height;
+    ballot.time = env.block.time;
+    if memo.is_some() {
+        ballot.memo = memo;
+    }
+    let res = if VOTERS.has(deps.storage, (id, &info.sender)) {
+        BALLOTS.save(deps.storage, (id, &info.sender), &ballot)?;
+        VOTE_BOX_LIST.save(deps.storage, id, &vote_box)?;
+        Response::new()
+    } else {
+        record_ballot(deps, &env, &info, &mut vote_box, ballot)?
+    };
+
+    Ok(res
+        .add_attribute("method", "quadratic vote given")
+        .add_attribute("option", vote_type.to_string())
+        .add_attribute("votes", votes)
+        .add_attribute("credits_left", credits - spent))
+}
+
+/// Address `delegator` hands its vote to on the votebox, a votebox delegation taking
+/// precedence over a global one
+fn delegate_of(storage: &dyn Storage, id: u64, delegator: &Addr) -> StdResult<Option<Addr>> {
+    match DELEGATIONS.may_load(storage, (id, delegator))? {
+        Some(delegate) => Ok(Some(delegate)),
+        None if id != GLOBAL_DELEGATION => {
+            DELEGATIONS.may_load(storage, (GLOBAL_DELEGATION, delegator))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Addresses met following the delegations on the votebox from `start`, `start` included
+fn delegation_chain(storage: &dyn Storage, id: u64, start: &Addr) -> StdResult<Vec<Addr>> {
+    let mut chain = vec![];
+    let mut next = Some(start.clone());
+    while let Some(address) = next {
+        if chain.contains(&address) {
+            break;
+        }
+        next = delegate_of(storage, id, &address)?;
+        chain.push(address);
+    }
+    Ok(chain)
+}
+
+/// Delegation chains are followed at most this many hops back from the voter
+const MAX_DELEGATION_DEPTH: u32 = 8;
+/// Delegations read while collecting the weight handed to a voter
+const MAX_DELEGATORS: usize = 50;
+
+/// Weight of everyone whose delegation chain on the votebox leads to `voter` without
+/// passing another address that voted. Delegators without voting power add nothing.
+/// Chains deeper than `MAX_DELEGATION_DEPTH` or more than `MAX_DELEGATORS` delegations
+/// fail the vote instead of dropping weight, until delegators undelegate.
+fn delegated_votes(
+    deps: Deps,
+    vote_box: &Vote,
+    voter: &Addr,
+) -> Result<Vec<(Addr, Uint128)>, ContractError> {
+    // merkle weights can only be proven by the delegator itself
+    if let VotingPower::Merkle { .. } = vote_box.voting_power {
+        return Ok(vec![]);
+    }
+    let id = vote_box.id.u64();
+    let mut votes = vec![];
+    let mut visited = vec![voter.clone()];
+    let mut delegates = vec![(voter.clone(), 0)];
+    let mut budget = MAX_DELEGATORS;
+    while let Some((delegate, depth)) = delegates.pop() {
+        for scope in [id, GLOBAL_DELEGATION] {
+            let delegators: StdResult<Vec<_>> = DELEGATORS
+                .prefix((scope, &delegate))
+                .keys(deps.storage, None, None, Order::Ascending)
+                .take(budget + 1)
+                .collect();
+            let delegators = delegators?;
+            if delegators.len() > budget {
+                return Err(ContractError::TooManyDelegators {
+                    max: MAX_DELEGATORS,
+                });
+            }
+            budget -= delegators.len();
+            for delegator in delegators {
+                if visited.contains(&delegator)
+                    || VOTERS.has(deps.storage, (id, &delegator))
+                    || delegate_of(deps.storage, id, &delegator)?.as_ref() != Some(&delegate)
+                {
+                    continue;
+                }
+                if depth == MAX_DELEGATION_DEPTH {
+                    return Err(ContractError::DelegationTooDeep {
+                        max: MAX_DELEGATION_DEPTH,
+                    });
+                }
+                visited.push(delegator.clone());
+                delegates.push((delegator.clone(), depth + 1));
+
+                if vote_box.allowlist && !ELIGIBLE_VOTERS.has(deps.storage, (id, &delegator)) {
+                    continue;
+                }
+                if let Ok(weight) = voting_power(deps, vote_box, &delegator, None) {
+                    votes.push((delegator, weight));
+                }
+            }
+        }
+    }
+    Ok(votes)
+}
+
+/// Takes the weight of `delegator` back out of the ballot it was counted in, if any
+fn release_delegated_vote(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    vote_box: &mut Vote,
+    delegator: &Addr,
+) -> Result<(), ContractError> {
+    let id = vote_box.id.u64();
+    if let Some(delegated) = DELEGATED_VOTES.may_load(storage, (id, delegator))? {
+        let mut ballot = BALLOTS.load(storage, (id, &delegated.delegate))?;
+        ballot.weight = ballot.weight.checked_sub(delegated.weight)?;
+        let option = vote_option(vote_box, ballot.vote_type, block)?;
+        option.count = option.count.checked_sub(delegated.weight)?;
+
+        BALLOTS.save(storage, (id, &delegated.delegate), &ballot)?;
+        DELEGATED_VOTES.remove(storage, (id, delegator));
+        COUNTED_DELEGATORS.remove(storage, (id, &delegated.delegate, delegator));
+    }
+    Ok(())
+}
+
+/// Checks that the votebox still accepts the ballot
+fn check_ballot(env: &Env, vote_box: &Vote, memo: &Option<String>) -> Result<(), ContractError> {
+    if let Some(memo) = memo {
+        if memo.len() > MAX_MEMO_LENGTH {
+            return Err(ContractError::MemoTooLong {
+                max: MAX_MEMO_LENGTH,
+            });
+        }
+    }
+    require_status(vote_box, &env.block, &[Status::Open {}])
+}
+
+/// Checks that the votebox is at one of the `allowed` stages. Open voteboxes that should
+/// have been closed report `Unexpired`, closed ones that should still be open `Expired`.
+fn require_status(
+    vote_box: &Vote,
+    block: &BlockInfo,
+    allowed: &[Status],
+) -> Result<(), ContractError> {
+    let status = current_status(vote_box, block);
+    if allowed.contains(&status) {
+        return Ok(());
+    }
+    match status {
+        Status::Open {} if allowed.contains(&Status::Closed {}) => Err(ContractError::Unexpired {}),
+        Status::Closed {} if allowed.contains(&Status::Open {}) => Err(ContractError::Expired {}),
+        status => Err(ContractError::InvalidStatus { status }),
+    }
+}
+
+/// Credits spent by a quadratic ballot
+fn spent_credits(ballot: &Ballot) -> StdResult<Uint128> {
+    ballot
+        .allocation
+        .iter()
+        .try_fold(Uint128::zero(), |total, votes| {
+            Ok(total.checked_add(votes.checked_mul(*votes)?)?)
+        })
+}
+
+/// Checks that the sender may still vote on the votebox and returns its voting weight
+fn voter_weight(
+    deps: Deps,
+    env: &Env,
+    info: &MessageInfo,
+    vote_box: &Vote,
+    proof: Option<MerkleProof>,
+    memo: &Option<String>,
+) -> Result<Uint128, ContractError> {
+    check_ballot(env, vote_box, memo)?;
+    let id = vote_box.id.u64();
+    if PENDING_VOTERS.has(deps.storage, id) {
+        return Err(ContractError::MigrationPending {});
+    }
+    if VOTERS.has(deps.storage, (id, &info.sender)) {
         return Err(ContractError::VoterRepeat {});
     }
+    if vote_box.allowlist && !ELIGIBLE_VOTERS.has(deps.storage, (id, &info.sender)) {
+        return Err(ContractError::NotEligible {});
+    }
+
+    voting_power(deps, vote_box, &info.sender, proof.as_ref())
+}
+
+/// Stores the sender's ballot on an already tallied votebox, locking the tokens behind it
+/// when the votebox asks for that
+fn record_ballot(
+    deps: DepsMut,
+    env: &Env,
+    info: &MessageInfo,
+    vote_box: &mut Vote,
+    ballot: Ballot,
+) -> Result<Response, ContractError> {
+    let res = lock_tokens(deps.storage, env, info, vote_box, ballot.weight)?;
+    save_ballot(deps.storage, info, vote_box, ballot)?;
+    Ok(res)
+}
+
+/// Pulls `weight` tokens of the voter into the contract on voteboxes locking cw20 votes
+fn lock_tokens(
+    storage: &mut dyn Storage,
+    env: &Env,
+    info: &MessageInfo,
+    vote_box: &Vote,
+    weight: Uint128,
+) -> Result<Response, ContractError> {
+    let mut res = Response::new();
+    if let VotingPower::Cw20Balance { token, lock: true } = &vote_box.voting_power {
+        LOCKED_TOKENS.update::<_, ContractError>(
+            storage,
+            (vote_box.id.u64(), &info.sender),
+            |locked| Ok(locked.unwrap_or_default().checked_add(weight)?),
+        )?;
+        res = res.add_message(WasmMsg::Execute {
+            contract_addr: token.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: info.sender.to_string(),
+                recipient: env.contract.address.to_string(),
+                amount: weight,
+            })?,
+            funds: vec![],
+        });
+    }
+    Ok(res)
+}
+
+fn save_ballot(
+    storage: &mut dyn Storage,
+    info: &MessageInfo,
+    vote_box: &mut Vote,
+    ballot: Ballot,
+) -> Result<(), ContractError> {
+    let id = vote_box.id.u64();
+    BALLOTS.save(storage, (id, &info.sender), &ballot)?;
+    VOTERS.save(storage, (id, &info.sender), &Empty {})?;
+    vote_box.voter_count = vote_box.voter_count.checked_add(Uint128::new(1))?;
+    VOTE_BOX_LIST.save(storage, id, vote_box)?;
+    Ok(())
+}
+
+pub fn execute_commit_vote(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
+    commitment: String,
+    proof: Option<MerkleProof>,
+    memo: Option<String>,
+) -> Result<Response, ContractError> {
+    let vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    if vote_box.reveal_deadline.is_none() {
+        return Err(ContractError::WrongVoteKind {});
+    }
+    if COMMITMENTS.has(deps.storage, (id.u64(), &info.sender)) {
+        return Err(ContractError::VoterRepeat {});
+    }
+    let weight = voter_weight(deps.as_ref(), &env, &info, &vote_box, proof, &memo)?;
+
+    let mut hash = [0u8; 32];
+    hex::decode_to_slice(&commitment, &mut hash)
+        .map_err(|_| ContractError::InvalidCommitment {})?;
+
+    // the weight is fixed and locked now, revealing only picks the option
+    let res = lock_tokens(deps.storage, &env, &info, &vote_box, weight)?;
+    COMMITMENTS.save(
+        deps.storage,
+        (id.u64(), &info.sender),
+        &Commitment {
+            hash: hex::encode(hash),
+            weight,
+            height: env.block.height,
+            time: env.block.time,
+            memo,
+        },
+    )?;
+
+    Ok(res
+        .add_attribute("method", "vote committed")
+        .add_attribute("voter", info.sender)
+        .add_attribute("weight", weight))
+}
+
+pub fn execute_reveal_vote(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
+    vote_type: i32,
+    salt: String,
+) -> Result<Response, ContractError> {
+    let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    let reveal_deadline = vote_box
+        .reveal_deadline
+        .ok_or(ContractError::WrongVoteKind {})?;
+    require_status(&vote_box, &env.block, &[Status::Closed {}])?;
+    if reveal_deadline.is_triggered(&env.block) {
+        return Err(ContractError::RevealClosed {});
+    }
+    let commitment = COMMITMENTS
+        .may_load(deps.storage, (id.u64(), &info.sender))?
+        .ok_or(ContractError::NotVoted {})?;
+
+    let preimage = format!("{}:{}:{}:{}", id, info.sender, vote_type, salt);
+    let hash = Sha256::digest(preimage.as_bytes());
+    if hex::encode(hash) != commitment.hash {
+        return Err(ContractError::InvalidReveal {});
+    }
+    let option = vote_option(&mut vote_box, vote_type, &env.block)?;
+    option.count = option.count.checked_add(commitment.weight)?;
+
+    COMMITMENTS.remove(deps.storage, (id.u64(), &info.sender));
+    let ballot = Ballot {
+        vote_type,
+        ranking: vec![],
+        approvals: vec![],
+        allocation: vec![],
+        weight: commitment.weight,
+        height: commitment.height,
+        time: commitment.time,
+        memo: commitment.memo,
+    };
+    save_ballot(deps.storage, &info, &mut vote_box, ballot)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "vote revealed")
+        .add_attribute("voter", info.sender)
+        .add_attribute("vote_type", vote_type.to_string())
+        .add_attribute("weight", commitment.weight))
+}
+
+pub fn execute_change_vote(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
+    vote_type: i32,
+) -> Result<Response, ContractError> {
+    let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    require_status(&vote_box, &env.block, &[Status::Open {}])?;
+    // ranked ballots are retracted and cast again instead
+    if vote_box.kind != (VoteKind::Single {}) {
+        return Err(ContractError::WrongVoteKind {});
+    }
+    let mut ballot = BALLOTS
+        .may_load(deps.storage, (id.u64(), &info.sender))?
+        .ok_or(ContractError::NotVoted {})?;
+
+    let old_option = vote_option(&mut vote_box, ballot.vote_type, &env.block)?;
+    old_option.count = old_option.count.checked_sub(ballot.weight)?;
+    let new_option = vote_option(&mut vote_box, vote_type, &env.block)?;
+    new_option.count = new_option.count.checked_add(ballot.weight)?;
+
+    let old_vote_type = ballot.vote_type;
+    ballot.vote_type = vote_type;
+    ballot.height = env.block.height;
+    ballot.time = env.block.time;
+    BALLOTS.save(deps.storage, (id.u64(), &info.sender), &ballot)?;
+    VOTE_BOX_LIST.save(deps.storage, id.u64(), &vote_box)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "vote changed")
+        .add_attribute("voter", info.sender)
+        .add_attribute("old_vote_type", old_vote_type.to_string())
+        .add_attribute("new_vote_type", vote_type.to_string())
+        .add_attribute("weight", ballot.weight))
+}
+
+pub fn execute_retract_vote(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
+) -> Result<Response, ContractError> {
+    let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    require_status(&vote_box, &env.block, &[Status::Open {}])?;
+    let ballot = BALLOTS
+        .may_load(deps.storage, (id.u64(), &info.sender))?
+        .ok_or(ContractError::NotVoted {})?;
+
+    for (vote_type, weight) in ballot_tallies(&ballot) {
+        let option = vote_option(&mut vote_box, vote_type, &env.block)?;
+        option.count = option.count.checked_sub(weight)?;
+    }
+    vote_box.voter_count = vote_box.voter_count.checked_sub(Uint128::new(1))?;
+
+    // delegators counted in the ballot are free to be counted again by another delegate
+    let delegators: StdResult<Vec<_>> = COUNTED_DELEGATORS
+        .prefix((id.u64(), &info.sender))
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect();
+    for delegator in delegators? {
+        DELEGATED_VOTES.remove(deps.storage, (id.u64(), &delegator));
+        COUNTED_DELEGATORS.remove(deps.storage, (id.u64(), &info.sender, &delegator));
+    }
+    VOTERS.remove(deps.storage, (id.u64(), &info.sender));
+    BALLOTS.remove(deps.storage, (id.u64(), &info.sender));
+    VOTE_BOX_LIST.save(deps.storage, id.u64(), &vote_box)?;
+
+    // a retracted vote no longer needs its tokens locked
+    let mut res = Response::new();
+    if let VotingPower::Cw20Balance { token, lock: true } = &vote_box.voting_power {
+        if let Some(locked) = LOCKED_TOKENS.may_load(deps.storage, (id.u64(), &info.sender))? {
+            LOCKED_TOKENS.remove(deps.storage, (id.u64(), &info.sender));
+            res = res.add_message(WasmMsg::Execute {
+                contract_addr: token.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: info.sender.to_string(),
+                    amount: locked,
+                })?,
+                funds: vec![],
+            });
+        }
+    }
+
+    Ok(res
+        .add_attribute("method", "vote retracted")
+        .add_attribute("voter", info.sender)
+        .add_attribute("old_vote_type", ballot.vote_type.to_string())
+        .add_attribute("weight", ballot.weight))
+}
+
+/// Weight the ballot added to each option it counts for
+fn ballot_tallies(ballot: &Ballot) -> Vec<(i32, Uint128)> {
+    if !ballot.allocation.is_empty() {
+        (0..)
+            .zip(ballot.allocation.iter().copied())
+            .filter(|(_, votes)| !votes.is_zero())
+            .collect()
+    } else if !ballot.approvals.is_empty() {
+        ballot
+            .approvals
+            .iter()
+            .map(|option| (*option as i32, ballot.weight))
+            .collect()
+    } else {
+        vec![(ballot.vote_type, ballot.weight)]
+    }
+}
+
+/// Option selected by `vote_type`, recording that its tally is about to change
+fn vote_option<'a>(
+    vote_box: &'a mut Vote,
+    vote_type: i32,
+    block: &BlockInfo,
+) -> Result<&'a mut VoteOption, ContractError> {
+    let index = usize::try_from(vote_type)
+        .ok()
+        .filter(|index| *index < vote_box.options.len())
+        .ok_or(ContractError::InvalidVote {})?;
+    vote_box.tally_updates += 1;
+    vote_box.last_tally_height = block.height;
+
+    let option = &mut vote_box.options[index];
+    option.reached_at = vote_box.tally_updates;
+    Ok(option)
+}
+
+const MIN_OPTIONS: usize = 2;
+const MAX_OPTIONS: usize = 20;
+
+pub fn execute_unlock(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
+) -> Result<Response, ContractError> {
+    let locked = LOCKED_TOKENS
+        .may_load(deps.storage, (id.u64(), &info.sender))?
+        .ok_or(ContractError::NothingLocked {})?;
+
+    let vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    require_status(
+        &vote_box,
+        &env.block,
+        &[
+            Status::Closed {},
+            Status::Finalized {},
+            Status::Executed {},
+            Status::Cancelled {},
+        ],
+    )?;
+    let token = match vote_box.voting_power {
+        VotingPower::Cw20Balance { token, .. } => token,
+        _ => return Err(ContractError::NothingLocked {}),
+    };
+
+    LOCKED_TOKENS.remove(deps.storage, (id.u64(), &info.sender));
+
+    let msg = WasmMsg::Execute {
+        contract_addr: token.to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Transfer {
+            recipient: info.sender.to_string(),
+            amount: locked,
+        })?,
+        funds: vec![],
+    };
+    Ok(Response::new()
+        .add_message(msg)
+        .add_attribute("action", "unlock")
+        .add_attribute("voter", info.sender)
+        .add_attribute("amount", locked))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn create_vote_box(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    deadline: Scheduled,
+    owner: String,
+    topic: String,
+    description: String,
+    create_date: String,
+    native_denom: Option<String>,
+    cw20_addr: Option<String>,
+    voting_power: Option<VotingPower>,
+    allowlist: Option<Vec<String>>,
+    options: Option<Vec<String>>,
+    kind: Option<VoteKind>,
+    reveal_deadline: Option<Scheduled>,
+    hide_tally_until_end: bool,
+    thresholds: Option<Thresholds>,
+    total_weight: Option<Uint128>,
+    tie_break: TieBreak,
+    draft: bool,
+    msgs: Vec<CosmosMsg>,
+    timelock: Option<Duration>,
+    guardian: Option<String>,
+) -> Result<Response, ContractError> {
+    let owner = deps.api.addr_validate(&owner)?;
+
+    let options = match options {
+        Some(labels) => {
+            let invalid = ContractError::InvalidOptions {
+                min: MIN_OPTIONS,
+                max: MAX_OPTIONS,
+            };
+            if labels.len() < MIN_OPTIONS || labels.len() > MAX_OPTIONS {
+                return Err(invalid);
+            }
+            let mut options: Vec<VoteOption> = Vec::with_capacity(labels.len());
+            for label in labels {
+                let label = label.trim().to_string();
+                if label.is_empty() || options.iter().any(|option| option.label == label) {
+                    return Err(invalid);
+                }
+                options.push(VoteOption {
+                    label,
+                    count: Uint128::zero(),
+                    reached_at: 0,
+                });
+            }
+            options
+        }
+        None => classic_options(),
+    };
+
+    if native_denom.is_some() && cw20_addr.is_some() {
+        return Err(ContractError::AmbiguousRewardToken {});
+    }
+    let cw20_addr = cw20_addr
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let voting_power = match voting_power.unwrap_or_default() {
+        VotingPower::Cw20Balance { token, lock } => VotingPower::Cw20Balance {
+            token: deps.api.addr_validate(token.as_str())?,
+            lock,
+        },
+        VotingPower::Cw4Group { group } => VotingPower::Cw4Group {
+            group: deps.api.addr_validate(group.as_str())?,
+        },
+        VotingPower::Merkle { root } => {
+            let mut root_hash = [0u8; 32];
+            hex::decode_to_slice(&root, &mut root_hash)
+                .map_err(|_| ContractError::InvalidMerkleRoot {})?;
+            VotingPower::Merkle { root }
+        }
+        other => other,
+    };
+    let kind = kind.unwrap_or_default();
+    if let VoteKind::Quadratic { credits } = kind {
+        if credits.is_zero() {
+            return Err(ContractError::InvalidCredits {});
+        }
+    }
+    if let Some(reveal_deadline) = reveal_deadline {
+        if kind != (VoteKind::Single {}) {
+            return Err(ContractError::UnsupportedSecretBallot {});
+        }
+        // mixing heights and times does not compare
+        if reveal_deadline.partial_cmp(&deadline) != Some(std::cmp::Ordering::Greater) {
+            return Err(ContractError::InvalidRevealDeadline {});
+        }
+    }
+
+    if let Some(thresholds) = &thresholds {
+        let one = Decimal::one();
+        if thresholds.quorum > one || thresholds.threshold > one || thresholds.veto_threshold > one
+        {
+            return Err(ContractError::InvalidThresholds {});
+        }
+        let labels = options.iter().map(|option| option.label.as_str());
+        if kind != (VoteKind::Single {}) || !labels.eq(CLASSIC_OPTIONS.iter().copied()) {
+            return Err(ContractError::UnsupportedThresholds {});
+        }
+        let derivable = match voting_power {
+            VotingPower::Cw4Group { .. } | VotingPower::Cw20Balance { .. } => true,
+            VotingPower::Equal {} => allowlist.is_some(),
+            _ => false,
+        };
+        if total_weight.is_none() && !derivable {
+            return Err(ContractError::MissingTotalWeight {});
+        }
+    }
+    // messages run with everything the contract holds, including other voteboxes' funds
+    if !msgs.is_empty() {
+        if ADMIN.may_load(deps.storage)? != Some(info.sender) {
+            return Err(ContractError::Unauthorized {});
+        }
+        // only passing rules can produce the `Passed` outcome that executes messages
+        if thresholds.is_none() {
+            return Err(ContractError::MessagesNeedThresholds {});
+        }
+        if total_weight.is_some() {
+            return Err(ContractError::FixedTotalWeight {});
+        }
+        // unlocked tokens can be voted with, passed on and voted with again
+        if let VotingPower::Cw20Balance { lock: false, .. } = voting_power {
+            return Err(ContractError::UnlockedVotingPower {});
+        }
+    }
+    if guardian.is_some() && timelock.is_none() {
+        return Err(ContractError::GuardianNeedsTimelock {});
+    }
+    let guardian = guardian
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+
+    let voteboxes: StdResult<Vec<_>> = VOTE_BOX_LIST
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect();
+
+    let votebox_found = voteboxes?
+        .into_iter()
+        .map(|list| list.1)
+        .find(|item| item.topic.to_lowercase() == topic.to_lowercase());
+    if votebox_found.is_some() {
+        return Err(ContractError::DuplicateVoteBox {});
+    }
+
+    let id = VOTE_BOX_SEQ.update::<_, StdError>(deps.storage, |id| Ok(id.add(Uint64::new(1))))?;
+
+    let new_vote_box = Vote {
+        id,
+        options,
+        kind,
+        status: if draft {
+            Status::Draft {}
+        } else {
+            Status::Open {}
+        },
+        deadline,
+        owner: owner.to_string(),
+        topic: topic.clone(),
+        description: description.clone(),
+        create_date: create_date.clone(),
+        total_amount: Uint128::zero(),
+        native_denom,
+        cw20_addr,
+        voter_count: Uint128::zero(),
+        voting_power,
+        allowlist: allowlist.is_some(),
+        reveal_deadline,
+        hide_tally_until_end,
+        thresholds,
+        total_weight,
+        tie_break,
+        casting_vote: None,
+        tally_updates: 0,
+        last_tally_height: 0,
+        payout: None,
+        final_result: None,
+        msgs,
+        timelock,
+        guardian,
+        executable_at: None,
+        vetoed: false,
+    };
+
+    VOTE_BOX_LIST.save(deps.storage, id.u64(), &new_vote_box)?;
+    for voter in allowlist.unwrap_or_default() {
+        let voter = deps.api.addr_validate(&voter)?;
+        ELIGIBLE_VOTERS.save(deps.storage, (id.u64(), &voter), &Empty {})?;
+    }
+    Ok(Response::new()
+        .add_attribute("create_vote", "success")
+        .add_attribute("print_id", id)
+        .add_attribute("owner", owner.clone())
+        .add_attribute("topic", topic.clone())
+        .add_attribute("description", description.clone()))
+}
+
+pub fn execute_cast_deciding_vote(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
+    option: u32,
+) -> Result<Response, ContractError> {
+    let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    if info.sender != vote_box.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if vote_box.tie_break != (TieBreak::OwnerCastingVote {}) {
+        return Err(ContractError::NoCastingVote {});
+    }
+    require_status(&vote_box, &env.block, &[Status::Closed {}])?;
+    if let Some(reveal_deadline) = vote_box.reveal_deadline {
+        if !reveal_deadline.is_triggered(&env.block) {
+            return Err(ContractError::Unexpired {});
+        }
+    }
+    if vote_box.casting_vote.is_some() {
+        return Err(ContractError::AlreadyDecided {});
+    }
+    if casting_vote_deadline(&vote_box).is_triggered(&env.block) {
+        return Err(ContractError::CastingVoteClosed {});
+    }
+    match count_votes(deps.as_ref(), &vote_box)?.1 {
+        Outcome::Tie { options } if options.contains(&option) => {}
+        _ => return Err(ContractError::NotTied {}),
+    }
+
+    vote_box.casting_vote = Some(option);
+    VOTE_BOX_LIST.save(deps.storage, id.u64(), &vote_box)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cast_deciding_vote")
+        .add_attribute("option", option.to_string()))
+}
+
+/// Delegations are followed when the delegate casts a single choice ballot. Changing them
+/// afterwards does not move weight that was already counted, but a direct vote by the
+/// delegator always does.
+pub fn execute_delegate(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Option<Uint64>,
+    delegate_to: String,
+) -> Result<Response, ContractError> {
+    let delegate = deps.api.addr_validate(&delegate_to)?;
+    let scope = match id {
+        Some(id) => {
+            let vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+            require_status(&vote_box, &env.block, &[Status::Draft {}, Status::Open {}])?;
+            id.u64()
+        }
+        None => GLOBAL_DELEGATION,
+    };
+
+    // following the delegations of the new delegate must not lead back to the sender
+    let chain = delegation_chain(deps.storage, scope, &delegate)?;
+    if chain.contains(&info.sender) {
+        return Err(ContractError::DelegationCycle {});
+    }
+    if scope == GLOBAL_DELEGATION {
+        // a votebox delegation along the chain takes it elsewhere on that votebox, which
+        // may lead back to the sender unless the sender delegated on the votebox as well
+        for address in &chain {
+            let boxes: StdResult<Vec<_>> = DELEGATED_BOXES
+                .prefix(address)
+                .keys(deps.storage, None, None, Order::Ascending)
+                .collect();
+            for id in boxes? {
+                if DELEGATIONS.has(deps.storage, (id, &info.sender)) {
+                    continue;
+                }
+                if delegation_chain(deps.storage, id, &delegate)?.contains(&info.sender) {
+                    return Err(ContractError::DelegationCycle {});
+                }
+            }
+        }
+    }
+
+    if let Some(previous) = DELEGATIONS.may_load(deps.storage, (scope, &info.sender))? {
+        DELEGATORS.remove(deps.storage, (scope, &previous, &info.sender));
+    }
+    DELEGATIONS.save(deps.storage, (scope, &info.sender), &delegate)?;
+    DELEGATORS.save(deps.storage, (scope, &delegate, &info.sender), &Empty {})?;
+    if scope != GLOBAL_DELEGATION {
+        DELEGATED_BOXES.save(deps.storage, (&info.sender, scope), &Empty {})?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "delegate")
+        .add_attribute("delegator", info.sender)
+        .add_attribute("delegate", delegate)
+        .add_attribute("scope", scope.to_string()))
+}
+
+pub fn execute_undelegate(
+    deps: DepsMut,
+    info: MessageInfo,
+    id: Option<Uint64>,
+) -> Result<Response, ContractError> {
+    let scope = id.map_or(GLOBAL_DELEGATION, |id| id.u64());
+    let delegate = DELEGATIONS
+        .may_load(deps.storage, (scope, &info.sender))?
+        .ok_or(ContractError::NotDelegated {})?;
+
+    DELEGATIONS.remove(deps.storage, (scope, &info.sender));
+    DELEGATORS.remove(deps.storage, (scope, &delegate, &info.sender));
+    DELEGATED_BOXES.remove(deps.storage, (&info.sender, scope));
+
+    Ok(Response::new()
+        .add_attribute("action", "undelegate")
+        .add_attribute("delegator", info.sender)
+        .add_attribute("delegate", delegate)
+        .add_attribute("scope", scope.to_string()))
+}
+
+pub fn execute_update_eligible_voters(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    let vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    if info.sender != vote_box.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    // the allowlist of a votebox carrying messages is its quorum, fixed once voting starts
+    let allowed: &[Status] = if vote_box.msgs.is_empty() {
+        &[Status::Draft {}, Status::Open {}]
+    } else {
+        &[Status::Draft {}]
+    };
+    require_status(&vote_box, &env.block, allowed)?;
+    if !vote_box.allowlist {
+        return Err(ContractError::NoAllowlist {});
+    }
+
+    for voter in &add {
+        let voter = deps.api.addr_validate(voter)?;
+        ELIGIBLE_VOTERS.save(deps.storage, (id.u64(), &voter), &Empty {})?;
+    }
+    // votes already cast by pruned addresses stay counted
+    for voter in &remove {
+        let voter = deps.api.addr_validate(voter)?;
+        ELIGIBLE_VOTERS.remove(deps.storage, (id.u64(), &voter));
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "update_eligible_voters")
+        .add_attribute("added", add.len().to_string())
+        .add_attribute("removed", remove.len().to_string()))
+}
+
+pub fn execute_deposit_native(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
+) -> Result<Response, ContractError> {
+    let mut votebox = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+
+    if info.sender != votebox.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    require_status(&votebox, &env.block, &[Status::Draft {}, Status::Open {}])?;
+
+    let denom = votebox
+        .native_denom
+        .clone()
+        .ok_or(ContractError::SendNativeTokens {})?;
+
+    let coin: &Coin = match info.funds.as_slice() {
+        [] => return Err(ContractError::SendNativeTokens {}),
+        [coin] if coin.denom == denom => coin,
+        _ => return Err(ContractError::NotSupportDenom {}),
+    };
+    if coin.amount.is_zero() {
+        return Err(ContractError::SendNativeTokens {});
+    }
+
+    votebox.total_amount = votebox.total_amount.checked_add(coin.amount)?;
+    VOTE_BOX_LIST.save(deps.storage, id.u64(), &votebox)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "deposit")
+        .add_attribute("deposited_amount", coin.amount)
+        .add_attribute("total_amount", votebox.total_amount))
+}
+
+pub fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let msg: ReceiveMsg = from_binary(&wrapper.msg)?;
+    let sender = deps.api.addr_validate(&wrapper.sender)?;
+    match msg {
+        ReceiveMsg::deposit { id } => {
+            execute_deposit_cw20(deps, env, info.sender, sender, wrapper.amount, id)
+        }
+    }
+}
+
+pub fn execute_deposit_cw20(
+    deps: DepsMut,
+    env: Env,
+    token: Addr,
+    sender: Addr,
+    amount: Uint128,
+    id: Uint64,
+) -> Result<Response, ContractError> {
+    let mut votebox = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+
+    if sender != votebox.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    require_status(&votebox, &env.block, &[Status::Draft {}, Status::Open {}])?;
+
+    if votebox.cw20_addr.as_ref() != Some(&token) {
+        return Err(ContractError::NotSupportToken {});
+    }
+
+    if amount.is_zero() {
+        return Err(ContractError::InsufficientBalance {});
+    }
+
+    votebox.total_amount = votebox.total_amount.checked_add(amount)?;
+    VOTE_BOX_LIST.save(deps.storage, id.u64(), &votebox)?;
+
+    Ok(Response::default()
+        .add_attribute("action", "deposit")
+        .add_attribute("token", token)
+        .add_attribute("deposited_amount", amount)
+        .add_attribute("total_amount", votebox.total_amount))
+}
+
+pub fn execute_claim(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
+) -> Result<Response, ContractError> {
+    let mut votebox = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+
+    // a veto cancels the messages, not the rewards of the voters
+    let allowed: &[Status] = if votebox.vetoed {
+        &[Status::Cancelled {}]
+    } else {
+        &[Status::Closed {}, Status::Finalized {}, Status::Executed {}]
+    };
+    require_status(&votebox, &env.block, allowed)?;
+    // voters of a secret ballot are only known once the reveal window closes
+    if let Some(reveal_deadline) = votebox.reveal_deadline {
+        if !reveal_deadline.is_triggered(&env.block) {
+            return Err(ContractError::Unexpired {});
+        }
+    }
+
+    if PENDING_VOTERS.has(deps.storage, id.u64()) {
+        return Err(ContractError::MigrationPending {});
+    }
+    if !VOTERS.has(deps.storage, (id.u64(), &info.sender)) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if CLAIMS.has(deps.storage, (id.u64(), &info.sender)) {
+        return Err(ContractError::AlreadyClaimed {});
+    }
+
+    if votebox.native_denom.is_none() && votebox.cw20_addr.is_none() {
+        return Err(ContractError::FreeVotes {});
+    }
+
+    // the pool and the voter list are frozen once the deadline passes, so the
+    // first claim fixes the share every voter receives
+    let amount = match votebox.payout {
+        Some(payout) => payout,
+        None => {
+            let payout = calc_amount(votebox.clone());
+            votebox.payout = Some(payout);
+            VOTE_BOX_LIST.save(deps.storage, id.u64(), &votebox)?;
+            payout
+        }
+    };
+
+    CLAIMS.save(deps.storage, (id.u64(), &info.sender), &amount)?;
+
+    let msg = reward_msg(deps.as_ref(), &env, &votebox, &info.sender, amount)?;
+    let res = Response::new()
+        .add_message(msg)
+        .add_attribute("action", "claim")
+        .add_attribute("claimer", info.sender)
+        .add_attribute("claimed_amount", amount);
+    Ok(res)
+}
+
+fn reward_msg(
+    deps: Deps,
+    env: &Env,
+    votebox: &Vote,
+    recipient: &Addr,
+    amount: Uint128,
+) -> Result<CosmosMsg, ContractError> {
+    if let Some(native) = votebox.native_denom.clone() {
+        let balance = deps
+            .querier
+            .query_balance(&env.contract.address, native.clone())?;
+        if amount.is_zero() || balance.amount < amount {
+            return Err(ContractError::InsufficientBalance {});
+        }
+        let msg = BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin {
+                denom: native,
+                amount,
+            }],
+        };
+        return Ok(CosmosMsg::Bank(msg));
+    }
+
+    let token = votebox
+        .cw20_addr
+        .clone()
+        .ok_or(ContractError::FreeVotes {})?;
+    let balance: BalanceResponse = deps.querier.query_wasm_smart(
+        &token,
+        &Cw20QueryMsg::Balance {
+            address: env.contract.address.to_string(),
+        },
+    )?;
+    if amount.is_zero() || balance.balance < amount {
+        return Err(ContractError::InsufficientBalance {});
+    }
+    let msg = WasmMsg::Execute {
+        contract_addr: token.to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Transfer {
+            recipient: recipient.to_string(),
+            amount,
+        })?,
+        funds: vec![],
+    };
+    Ok(CosmosMsg::Wasm(msg))
+}
+
+pub fn calc_amount(votebox: Vote) -> Uint128 {
+    votebox
+        .total_amount
+        .checked_div(votebox.voter_count)
+        .unwrap_or_default()
+}
+
+/// Records the outcome of a closed votebox once and for all. The votebox accepts no more
+/// changes afterwards, only reward claims and unlocking.
+pub fn execute_finalize(deps: DepsMut, env: Env, id: Uint64) -> Result<Response, ContractError> {
+    let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    require_status(&vote_box, &env.block, &[Status::Closed {}])?;
+    if let Some(reveal_deadline) = vote_box.reveal_deadline {
+        if !reveal_deadline.is_triggered(&env.block) {
+            return Err(ContractError::Unexpired {});
+        }
+    }
 
-    match vote_type {
-        0 => vote_box.no_count = vote_box.no_count.checked_add(Uint128::new(1))?,
-        1 => vote_box.abstain_count = vote_box.abstain_count.checked_add(Uint128::new(1))?,
-        2 => vote_box.yes_count = vote_box.yes_count.checked_add(Uint128::new(1))?,
-        3 => {
-            vote_box.no_with_veto_count =
-                vote_box.no_with_veto_count.checked_add(Uint128::new(1))?
+    let (_, outcome, mut decided_by) = tally_outcome(deps.as_ref(), &vote_box)?;
+    if let (Outcome::Tie { .. }, TieBreak::OwnerCastingVote {}) = (&outcome, &vote_box.tie_break) {
+        if !casting_vote_deadline(&vote_box).is_triggered(&env.block) {
+            return Err(ContractError::AwaitingCastingVote {});
         }
-        _ => return Err(ContractError::InvalidVote {}),
+        // the owner let the window pass, so the tie stays with the status quo
+        decided_by = Some(TieBreak::StatusQuo {});
     }
+    let result = FinalResult {
+        outcome,
+        decided_by,
+        totals: vote_box.options.iter().map(|o| o.count).collect(),
+        voter_count: vote_box.voter_count,
+        height: env.block.height,
+        time: env.block.time,
+    };
+    let outcome = String::from_utf8_lossy(&to_vec(&result.outcome)?).into_owned();
+    if result.outcome == (Outcome::Passed {}) {
+        vote_box.executable_at = vote_box.timelock.map(|timelock| timelock.after(&env.block));
+    }
+    vote_box.status = Status::Finalized {};
+    vote_box.final_result = Some(result);
+    VOTE_BOX_LIST.save(deps.storage, id.u64(), &vote_box)?;
+
+    let event = Event::new("votebox_finalized")
+        .add_attribute("id", id)
+        .add_attribute("outcome", outcome)
+        .add_attribute("voter_count", vote_box.voter_count)
+        .add_attribute("height", env.block.height.to_string());
+    Ok(Response::new()
+        .add_event(event)
+        .add_attribute("method", "finalize")
+        .add_attribute("id", id))
+}
 
-    vote_box.voters.push(info.sender);
-    vote_box.voter_count = vote_box.voter_count.checked_add(Uint128::new(1))?;
+pub fn execute_proposal(deps: DepsMut, env: Env, id: Uint64) -> Result<Response, ContractError> {
+    let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    if vote_box.vetoed {
+        return Err(ContractError::Vetoed {});
+    }
+    require_status(&vote_box, &env.block, &[Status::Finalized {}])?;
+    match &vote_box.final_result {
+        Some(FinalResult {
+            outcome: Outcome::Passed {},
+            ..
+        }) => {}
+        _ => return Err(ContractError::NotPassed {}),
+    }
+    if let Some(executable_at) = vote_box.executable_at {
+        if !executable_at.is_expired(&env.block) {
+            return Err(ContractError::Timelocked {});
+        }
+    }
 
-    VOTE_BOX_LIST.save(deps.storage, id.u64(), &vote_box);
+    vote_box.status = Status::Executed {};
+    VOTE_BOX_LIST.save(deps.storage, id.u64(), &vote_box)?;
 
     Ok(Response::new()
-        .add_attribute("method", "vote given")
-        .add_attribute("yes_count", vote_box.yes_count)
-        .add_attribute("no count", vote_box.no_count)
-        .add_attribute("abstain_count", vote_box.abstain_count)
-        .add_attribute("no_with_veto_count", vote_box.no_with_veto_count))
+        .add_messages(vote_box.msgs)
+        .add_attribute("method", "execute_proposal")
+        .add_attribute("id", id))
 }
 
-pub fn create_vote_box(
+pub fn execute_veto(
     deps: DepsMut,
-    _env: Env,
-    _info: MessageInfo,
-    deadline: Scheduled,
-    owner: String,
-    topic: String,
-    description: String,
-    create_date: String,
-    native_denom: Option<String>,
+    env: Env,
+    info: MessageInfo,
+    id: Uint64,
 ) -> Result<Response, ContractError> {
-    let owner = deps.api.addr_validate(&owner)?;
-
-    let voteboxes: StdResult<Vec<_>> = VOTE_BOX_LIST
-        .range(deps.storage, None, None, Order::Ascending)
-        .collect();
-
-    let votebox_found = voteboxes?.into_iter().map(|list| list.1).find(|item|item.topic.to_lowercase() == topic.to_lowercase());
-    if votebox_found.is_some() {
-        return Err(ContractError::DuplicateVoteBox {});
+    let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    if vote_box.guardian.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if vote_box.vetoed {
+        return Err(ContractError::Vetoed {});
+    }
+    require_status(&vote_box, &env.block, &[Status::Finalized {}])?;
+    // only passed voteboxes get a timelock to veto in
+    let executable_at = vote_box.executable_at.ok_or(ContractError::NotPassed {})?;
+    if executable_at.is_expired(&env.block) {
+        return Err(ContractError::VetoClosed {});
     }
 
+    vote_box.vetoed = true;
+    vote_box.status = Status::Cancelled {};
+    VOTE_BOX_LIST.save(deps.storage, id.u64(), &vote_box)?;
 
-    let id = VOTE_BOX_SEQ.update::<_, StdError>(deps.storage, |id| Ok(id.add(Uint64::new(1))))?;
-
-    let new_vote_box = Vote {
-        id,
-        yes_count: Uint128::zero(),
-        no_count: Uint128::zero(),
-        abstain_count: Uint128::zero(),
-        no_with_veto_count: Uint128::zero(),
-        deadline: deadline.clone(),
-        owner: owner.to_string(),
-        topic: topic.clone(),
-        description: description.clone(),
-        create_date: create_date.clone(),
-        total_amount: Uint128::zero(),
-        native_denom,
-        voters: vec![],
-        voter_count: Uint128::zero(),
-    };
-
-    VOTE_BOX_LIST.save(deps.storage, id.u64(), &new_vote_box)?;
     Ok(Response::new()
-        .add_attribute("create_vote", "success")
-        .add_attribute("print_id", id)
-        .add_attribute("owner", owner.clone())
-        .add_attribute("topic", topic.clone())
-        .add_attribute("description", description.clone()))
+        .add_attribute("method", "veto_execution")
+        .add_attribute("id", id)
+        .add_attribute("guardian", info.sender))
 }
 
-pub fn execute_deposit_native(
+pub fn open_votebox(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     id: Uint64,
 ) -> Result<Response, ContractError> {
-    let mut votebox = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
-
-    if info.sender != votebox.owner {
+    let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    if info.sender != vote_box.owner {
         return Err(ContractError::Unauthorized {});
     }
-
-    if votebox.deadline.is_triggered(&env.block) {
+    require_status(&vote_box, &env.block, &[Status::Draft {}])?;
+    if vote_box.deadline.is_triggered(&env.block) {
         return Err(ContractError::Expired {});
     }
 
-    let denom = votebox
-        .native_denom
-        .clone()
-        .ok_or(ContractError::SendNativeTokens {})?;
-
-    let coin: &Coin = info
-        .funds
-        .iter()
-        .find(|c| c.denom == denom)
-        .ok_or(ContractError::NotSupportDenom {})?;
-
-    votebox.total_amount = votebox.total_amount.checked_add(coin.amount)?;
-    VOTE_BOX_LIST.save(deps.storage, id.u64(), &votebox)?;
+    vote_box.status = Status::Open {};
+    VOTE_BOX_LIST.save(deps.storage, id.u64(), &vote_box)?;
 
-    Ok(Response::default()
-        .add_attribute("action", "deposit")
-        .add_attribute("deposited_amount", coin.amount)
-        .add_attribute("total_amount", votebox.total_amount))
+    Ok(Response::new()
+        .add_attribute("method", "vote_open")
+        .add_attribute("id", id))
 }
 
-pub fn execute_claim(
+pub fn cancel_votebox(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     id: Uint64,
 ) -> Result<Response, ContractError> {
-    let mut votebox = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
-
-    if !votebox.deadline.is_triggered(&env.block) {
-        return Err(ContractError::Unexpired {});
+    let mut vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    if info.sender != vote_box.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    require_status(&vote_box, &env.block, &[Status::Draft {}, Status::Open {}])?;
+
+    vote_box.status = Status::Cancelled {};
+    VOTE_BOX_LIST.save(deps.storage, id.u64(), &vote_box)?;
+
+    // voters get nothing from a cancelled votebox, so the rewards go back to the owner.
+    // Locked tokens can be unlocked right away.
+    let mut res = Response::new();
+    if !vote_box.total_amount.is_zero() {
+        let owner = deps.api.addr_validate(&vote_box.owner)?;
+        let msg = reward_msg(
+            deps.as_ref(),
+            &env,
+            &vote_box,
+            &owner,
+            vote_box.total_amount,
+        )?;
+        res = res.add_message(msg);
     }
 
-    let index = votebox
-        .voters
-        .iter()
-        .position(|x| *x == info.sender)
-        .ok_or(ContractError::Unauthorized {})?;
-
-    let amount = calc_amount(votebox.clone());
-
-    let msg: CosmosMsg = match votebox.native_denom.clone() {
-        None => Err(ContractError::FreeVotes {}),
-        Some(native) => {
-            let balance = deps
-                .querier
-                .query_balance(env.contract.address, native.clone())?;
-            if balance.amount < amount {
-                return Err(ContractError::InsufficientBalance {});
-            }
-            let msg = BankMsg::Send {
-                to_address: votebox.voters.remove(index).to_string(),
-                amount: vec![Coin {
-                    denom: native,
-                    amount,
-                }],
-            };
-            Ok(CosmosMsg::Bank(msg))
-        }
-    }?;
-    VOTE_BOX_LIST.save(deps.storage, id.u64(), &votebox)?;
-    let res = Response::new().add_message(msg);
-    Ok(res)
+    Ok(res
+        .add_attribute("method", "vote_cancel")
+        .add_attribute("id", id)
+        .add_attribute("refunded", vote_box.total_amount))
 }
 
-pub fn calc_amount(votebox: Vote) -> Uint128 {
-    return votebox.total_amount / votebox.voter_count;
+/// Per-voter entries a reset or removal clears in one transaction
+const MAX_CLEARED_ENTRIES: usize = 500;
+
+/// Entries stored under votebox `id` in `map`, taken off a `budget` shared by everything
+/// cleared in the same transaction
+fn votebox_entries<'a, T>(
+    storage: &dyn Storage,
+    map: &Map<'a, (u64, &'a Addr), T>,
+    id: u64,
+    budget: &mut usize,
+) -> Result<Vec<(Addr, T)>, ContractError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let entries: StdResult<Vec<_>> = map
+        .prefix(id)
+        .range(storage, None, None, Order::Ascending)
+        .take(*budget + 1)
+        .collect();
+    let entries = entries?;
+    if entries.len() > *budget {
+        return Err(ContractError::TooManyToClear {
+            max: MAX_CLEARED_ENTRIES,
+        });
+    }
+    *budget -= entries.len();
+    Ok(entries)
 }
 
 #[allow(unused_must_use)]
@@ -253,23 +1744,35 @@ pub fn reset(
         return Err(ContractError::Unauthorized {});
     }
 
-    if vote_box.deadline.is_triggered(&env.block) {
-        return Err(ContractError::Expired {});
+    require_status(&vote_box, &env.block, &[Status::Draft {}, Status::Open {}])?;
+
+    // everything is cleared in one transaction, so popular voteboxes can't be reset
+    let mut budget = MAX_CLEARED_ENTRIES;
+    let voters = votebox_entries(deps.storage, &VOTERS, id.u64(), &mut budget)?;
+    let committed = votebox_entries(deps.storage, &COMMITMENTS, id.u64(), &mut budget)?;
+    let delegators = votebox_entries(deps.storage, &DELEGATED_VOTES, id.u64(), &mut budget)?;
+
+    for option in vote_box.options.iter_mut() {
+        option.count = Uint128::zero();
     }
-    vote_box.yes_count = Uint128::zero();
-    vote_box.no_count = Uint128::zero();
-    vote_box.abstain_count = Uint128::zero();
-    vote_box.no_with_veto_count = Uint128::zero();
     vote_box.voter_count = Uint128::zero();
-    vote_box.voters = vec![];
+    for (voter, _) in voters {
+        VOTERS.remove(deps.storage, (id.u64(), &voter));
+        BALLOTS.remove(deps.storage, (id.u64(), &voter));
+    }
+    for (voter, _) in committed {
+        COMMITMENTS.remove(deps.storage, (id.u64(), &voter));
+    }
+    // legacy voters still waiting for `migrate_voters` are cleared as well
+    PENDING_VOTERS.remove(deps.storage, id.u64());
+    for (delegator, delegated) in delegators {
+        DELEGATED_VOTES.remove(deps.storage, (id.u64(), &delegator));
+        COUNTED_DELEGATORS.remove(deps.storage, (id.u64(), &delegated.delegate, &delegator));
+    }
 
     VOTE_BOX_LIST.save(deps.storage, id.u64(), &vote_box);
     Ok(Response::new()
         .add_attribute("method", "vote_reset")
-        .add_attribute("yes_count", vote_box.yes_count)
-        .add_attribute("no_count", vote_box.no_count)
-        .add_attribute("abstain_count", vote_box.abstain_count)
-        .add_attribute("no_with_veto_count", vote_box.no_with_veto_count)
         .add_attribute("caller", info.sender.to_string()))
 }
 
@@ -283,15 +1786,85 @@ pub fn remove_votebox(
     if info.sender != vote_box.owner {
         return Err(ContractError::Unauthorized {});
     }
-    if vote_box.deadline.is_triggered(&env.block) {
-        return Err(ContractError::Expired {});
-    }
+    require_status(
+        &vote_box,
+        &env.block,
+        &[Status::Draft {}, Status::Open {}, Status::Cancelled {}],
+    )?;
+
+    // everything kept per voter goes with the votebox, within the same bound as a reset
+    let mut budget = MAX_CLEARED_ENTRIES;
+    let voters = votebox_entries(deps.storage, &VOTERS, id.u64(), &mut budget)?;
+    let ballots = votebox_entries(deps.storage, &BALLOTS, id.u64(), &mut budget)?;
+    let claims = votebox_entries(deps.storage, &CLAIMS, id.u64(), &mut budget)?;
+    let committed = votebox_entries(deps.storage, &COMMITMENTS, id.u64(), &mut budget)?;
+    let eligible = votebox_entries(deps.storage, &ELIGIBLE_VOTERS, id.u64(), &mut budget)?;
+    let delegations = votebox_entries(deps.storage, &DELEGATIONS, id.u64(), &mut budget)?;
+    let delegated = votebox_entries(deps.storage, &DELEGATED_VOTES, id.u64(), &mut budget)?;
+    let locked = votebox_entries(deps.storage, &LOCKED_TOKENS, id.u64(), &mut budget)?;
 
     // alttaki satır isleyince son id bir eksildigi icin ayni id ile tekrar votebox olusturmak deneniyo
     //VOTE_BOX_SEQ.update::<_, StdError>(deps.storage, |id| Ok(id.checked_sub(Uint64::new(1))?));
     VOTE_BOX_LIST.remove(deps.storage, vote_box.id.u64());
+    PENDING_VOTERS.remove(deps.storage, id.u64());
+    for (voter, _) in voters {
+        VOTERS.remove(deps.storage, (id.u64(), &voter));
+    }
+    for (voter, _) in ballots {
+        BALLOTS.remove(deps.storage, (id.u64(), &voter));
+    }
+    for (voter, _) in claims {
+        CLAIMS.remove(deps.storage, (id.u64(), &voter));
+    }
+    for (voter, _) in committed {
+        COMMITMENTS.remove(deps.storage, (id.u64(), &voter));
+    }
+    for (voter, _) in eligible {
+        ELIGIBLE_VOTERS.remove(deps.storage, (id.u64(), &voter));
+    }
+    for (delegator, delegate) in delegations {
+        DELEGATIONS.remove(deps.storage, (id.u64(), &delegator));
+        DELEGATORS.remove(deps.storage, (id.u64(), &delegate, &delegator));
+        DELEGATED_BOXES.remove(deps.storage, (&delegator, id.u64()));
+    }
+    for (delegator, delegated) in delegated {
+        DELEGATED_VOTES.remove(deps.storage, (id.u64(), &delegator));
+        COUNTED_DELEGATORS.remove(deps.storage, (id.u64(), &delegated.delegate, &delegator));
+    }
+
+    // rewards of a votebox that was not cancelled go back to the owner, as in a cancel
+    let mut refunds: Vec<CosmosMsg> = vec![];
+    let cancelled = vote_box.status == (Status::Cancelled {});
+    if !cancelled && !vote_box.total_amount.is_zero() {
+        let owner = deps.api.addr_validate(&vote_box.owner)?;
+        refunds.push(reward_msg(
+            deps.as_ref(),
+            &env,
+            &vote_box,
+            &owner,
+            vote_box.total_amount,
+        )?);
+    }
+    // tokens locked by voters go back to them, as the box can no longer be unlocked
+    if let VotingPower::Cw20Balance { token, lock: true } = &vote_box.voting_power {
+        for (voter, amount) in locked {
+            LOCKED_TOKENS.remove(deps.storage, (id.u64(), &voter));
+            refunds.push(
+                WasmMsg::Execute {
+                    contract_addr: token.to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: voter.to_string(),
+                        amount,
+                    })?,
+                    funds: vec![],
+                }
+                .into(),
+            );
+        }
+    }
 
     Ok(Response::new()
+        .add_messages(refunds)
         .add_attribute("method: ", "votebox deleted")
         .add_attribute("deleted id: ", id))
 }
@@ -299,46 +1872,103 @@ pub fn remove_votebox(
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
-        QueryMsg::query_vote { id } => to_binary(&query_vote(deps, id)?),
+        QueryMsg::query_vote { id } => to_binary(&query_vote(deps, env, id)?),
         QueryMsg::get_list { start_after, limit } => {
-            to_binary(&query_votelist(deps, start_after, limit)?)
+            to_binary(&query_votelist(deps, env, start_after, limit)?)
         }
         QueryMsg::get_votebox_count {} => to_binary(&query_votebox_count(deps)?),
         QueryMsg::get_vbop_count {} => to_binary(&query_votebox_count(deps)?),
         QueryMsg::get_voteboxes_by_owner { owner } => {
-            to_binary(&query_voteboxes_by_owner(deps, owner)?)
+            to_binary(&query_voteboxes_by_owner(deps, env, owner)?)
         }
         QueryMsg::get_voteboxes_by_topic { topic } => {
-            to_binary( &query_votebox_topics(deps, &topic)?)
+            to_binary(&query_votebox_topics(deps, env, &topic)?)
         }
         QueryMsg::get_statistics {} => to_binary(&query_stats(deps, env)?),
+        QueryMsg::query_claim_status { id, address } => {
+            to_binary(&query_claim_status(deps, id, address)?)
+        }
+        QueryMsg::list_eligible_voters {
+            id,
+            start_after,
+            limit,
+        } => to_binary(&query_eligible_voters(deps, id, start_after, limit)?),
+        QueryMsg::query_credits { id, voter } => to_binary(&query_credits(deps, id, voter)?),
+        QueryMsg::query_result { id } => to_binary(&query_result(deps, env, id)?),
+        QueryMsg::query_ballot { id, voter } => to_binary(&query_ballot(deps, env, id, voter)?),
+        QueryMsg::list_ballots {
+            id,
+            start_after,
+            limit,
+        } => to_binary(&query_ballots(deps, env, id, start_after, limit)?),
+        QueryMsg::list_delegators {
+            id,
+            delegate,
+            start_after,
+            limit,
+        } => to_binary(&query_delegators(deps, id, delegate, start_after, limit)?),
     }
 }
 
-pub fn query_vote(deps: Deps, id: Uint64) -> StdResult<VoteResponse> {
+pub fn query_vote(deps: Deps, env: Env, id: Uint64) -> StdResult<VoteResponse> {
     let vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
-    let res = VoteResponse {
-        id: vote_box.id,
-        yes_count: vote_box.yes_count,
-        no_count: vote_box.no_count,
-        abstain_count: vote_box.abstain_count,
-        no_with_veto_count: vote_box.no_with_veto_count,
-        deadline: vote_box.deadline,
-        owner: vote_box.owner,
-        topic: vote_box.topic,
-        create_date: vote_box.create_date,
-        description: vote_box.description,
-        native_denom: vote_box.native_denom,
-        total_amount: vote_box.total_amount,
+    Ok(vote_response(vote_box, &env.block))
+}
+
+/// Whether the votebox still keeps its tallies out of queries
+fn tally_hidden(vote_box: &Vote, block: &BlockInfo) -> bool {
+    vote_box.hide_tally_until_end && !vote_box.deadline.is_triggered(block)
+}
+
+/// Votebox as reported by queries, with hidden tallies zeroed
+fn vote_response(vote_box: Vote, block: &BlockInfo) -> VoteResponse {
+    let hidden = tally_hidden(&vote_box, block);
+    let status = current_status(&vote_box, block);
+    let mut res = VoteResponse {
+        status,
+        ..vote_box.into()
     };
-    Ok(res)
+    if hidden {
+        for option in res.options.iter_mut() {
+            option.count = Uint128::zero();
+        }
+    }
+    res
+}
+
+pub fn query_claim_status(
+    deps: Deps,
+    id: Uint64,
+    address: String,
+) -> StdResult<ClaimStatusResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    let voted = VOTERS.has(deps.storage, (id.u64(), &address));
+    let claimed = CLAIMS.may_load(deps.storage, (id.u64(), &address))?;
+
+    let amount = match (voted, claimed) {
+        (_, Some(amount)) => amount,
+        (false, None) => Uint128::zero(),
+        (true, None) => match vote_box.payout {
+            Some(payout) => payout,
+            None => calc_amount(vote_box),
+        },
+    };
+
+    Ok(ClaimStatusResponse {
+        voted,
+        claimed: claimed.is_some(),
+        amount,
+    })
 }
+
 // settings for pagination
 const MAX_LIMIT: u32 = 30;
 const DEFAULT_LIMIT: u32 = 10;
 
 pub fn query_votelist(
     deps: Deps,
+    env: Env,
     start_after: Option<u64>,
     limit: Option<u32>,
 ) -> StdResult<VoteBoxListResponse> {
@@ -350,11 +1980,213 @@ pub fn query_votelist(
         .collect();
 
     let res = VoteBoxListResponse {
-        voteList: votes?.into_iter().map(|l| l.1.into()).collect(),
+        voteList: votes?
+            .into_iter()
+            .map(|l| vote_response(l.1, &env.block))
+            .collect(),
+    };
+    Ok(res)
+}
+
+pub fn query_eligible_voters(
+    deps: Deps,
+    id: Uint64,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<EligibleVotersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let start = start_after.as_ref().map(Bound::exclusive);
+    let voters: StdResult<Vec<_>> = ELIGIBLE_VOTERS
+        .prefix(id.u64())
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect();
+
+    let res = EligibleVotersResponse {
+        voters: voters?.into_iter().map(String::from).collect(),
+    };
+    Ok(res)
+}
+
+pub fn query_delegators(
+    deps: Deps,
+    id: Option<Uint64>,
+    delegate: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<DelegatorsResponse> {
+    let scope = id.map_or(GLOBAL_DELEGATION, |id| id.u64());
+    let delegate = deps.api.addr_validate(&delegate)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let start = start_after.as_ref().map(Bound::exclusive);
+    let delegators: StdResult<Vec<_>> = DELEGATORS
+        .prefix((scope, &delegate))
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect();
+
+    let res = DelegatorsResponse {
+        delegators: delegators?.into_iter().map(String::from).collect(),
+    };
+    Ok(res)
+}
+
+pub fn query_credits(deps: Deps, id: Uint64, voter: String) -> StdResult<CreditsResponse> {
+    let vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    let budget = match vote_box.kind {
+        VoteKind::Quadratic { credits } => credits,
+        _ => {
+            return Err(StdError::generic_err(
+                "VoteBox does not use quadratic voting",
+            ))
+        }
+    };
+    let voter = deps.api.addr_validate(&voter)?;
+    let spent = match BALLOTS.may_load(deps.storage, (id.u64(), &voter))? {
+        Some(ballot) => spent_credits(&ballot)?,
+        None => Uint128::zero(),
+    };
+    Ok(CreditsResponse {
+        budget,
+        spent,
+        remaining: budget.checked_sub(spent)?,
+    })
+}
+
+pub fn query_result(deps: Deps, env: Env, id: Uint64) -> StdResult<ResultResponse> {
+    let vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    match current_status(&vote_box, &env.block) {
+        Status::Draft {} | Status::Open {} => {
+            return Err(StdError::generic_err("VoteBox is still open"))
+        }
+        Status::Cancelled {} => return Err(StdError::generic_err("VoteBox was cancelled")),
+        _ => {}
+    }
+
+    let (rounds, outcome, decided_by) = match &vote_box.final_result {
+        Some(result) => {
+            let (rounds, _) = count_votes(deps, &vote_box)?;
+            (rounds, result.outcome.clone(), result.decided_by.clone())
+        }
+        None => tally_outcome(deps, &vote_box)?,
+    };
+
+    let mut unrevealed = 0u64;
+    let mut unrevealed_weight = Uint128::zero();
+    for commitment in COMMITMENTS
+        .prefix(id.u64())
+        .range(deps.storage, None, None, Order::Ascending)
+    {
+        let (_, commitment) = commitment?;
+        unrevealed += 1;
+        unrevealed_weight = unrevealed_weight.checked_add(commitment.weight)?;
+    }
+
+    Ok(ResultResponse {
+        id,
+        rounds,
+        outcome,
+        decided_by,
+        unrevealed,
+        unrevealed_weight,
+    })
+}
+
+/// Counting rounds and outcome of a closed votebox, with ties broken or the passing rules
+/// applied
+fn tally_outcome(
+    deps: Deps,
+    vote_box: &Vote,
+) -> StdResult<(Vec<RunoffRound>, Outcome, Option<TieBreak>)> {
+    let (rounds, outcome) = count_votes(deps, vote_box)?;
+    let (outcome, decided_by) = match &vote_box.thresholds {
+        Some(thresholds) => {
+            let counts = [0, 1, 2, 3].map(|i| vote_box.options[i].count);
+            let total_weight = eligible_weight(deps, vote_box)?;
+            (passing_outcome(counts, total_weight, thresholds)?, None)
+        }
+        None => break_tie(vote_box, outcome),
+    };
+    Ok((rounds, outcome, decided_by))
+}
+
+/// Counting rounds and plurality outcome of the votebox, before ties are broken
+fn count_votes(deps: Deps, vote_box: &Vote) -> StdResult<(Vec<RunoffRound>, Outcome)> {
+    match vote_box.kind {
+        // approval and quadratic tallies already hold the weight of every ballot
+        VoteKind::Single {} | VoteKind::Approval {} | VoteKind::Quadratic { .. } => {
+            let tallies: Vec<Uint128> = vote_box.options.iter().map(|o| o.count).collect();
+            let outcome = get_winner(&tallies);
+            let round = RunoffRound {
+                tallies,
+                exhausted: Uint128::zero(),
+                eliminated: vec![],
+            };
+            Ok((vec![round], outcome))
+        }
+        VoteKind::RankedChoice {} => {
+            let ballots: StdResult<Vec<_>> = BALLOTS
+                .prefix(vote_box.id.u64())
+                .range(deps.storage, None, None, Order::Ascending)
+                .map(|item| item.map(|(_, ballot)| (ballot.ranking, ballot.weight)))
+                .collect();
+            Ok(instant_runoff(vote_box.options.len(), &ballots?)?)
+        }
+    }
+}
+
+pub fn query_ballot(deps: Deps, env: Env, id: Uint64, voter: String) -> StdResult<BallotResponse> {
+    check_ballots_visible(deps, &env, id)?;
+    let voter = deps.api.addr_validate(&voter)?;
+    let ballot = BALLOTS.load(deps.storage, (id.u64(), &voter))?;
+    Ok(BallotResponse::new(voter, ballot))
+}
+
+pub fn query_ballots(
+    deps: Deps,
+    env: Env,
+    id: Uint64,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<BallotListResponse> {
+    check_ballots_visible(deps, &env, id)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    let start = start_after.as_ref().map(Bound::exclusive);
+    let ballots: StdResult<Vec<_>> = BALLOTS
+        .prefix(id.u64())
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect();
+
+    let res = BallotListResponse {
+        ballots: ballots?
+            .into_iter()
+            .map(|(voter, ballot)| BallotResponse::new(voter, ballot))
+            .collect(),
     };
     Ok(res)
 }
 
+/// Ballots would give away a hidden tally
+fn check_ballots_visible(deps: Deps, env: &Env, id: Uint64) -> StdResult<()> {
+    let vote_box = VOTE_BOX_LIST.load(deps.storage, id.u64())?;
+    if tally_hidden(&vote_box, &env.block) {
+        return Err(StdError::generic_err(
+            "Ballots are hidden until the VoteBox deadline",
+        ));
+    }
+    Ok(())
+}
+
 pub fn query_votebox_count(deps: Deps) -> StdResult<VBCountResponse> {
     let res = VBCountResponse {
         count: VOTE_BOX_SEQ.load(deps.storage)?,
@@ -367,15 +2199,17 @@ pub fn query_vb_open_closed(deps: Deps, env: Env) -> StdResult<VBOCResponse> {
         .range(deps.storage, None, None, Order::Ascending)
         .collect();
 
-    let bisi: Vec<VoteResponse> = votes?.into_iter().map(|l| l.1.into()).collect();
     let mut open = Uint64::zero();
     let mut closed = Uint64::zero();
 
-    for i in bisi {
-        if i.deadline.is_triggered(&env.block) {
-            closed += Uint64::new(1);
-        } else {
-            open += Uint64::new(1);
+    // drafts and cancelled voteboxes are neither
+    for (_, vote_box) in votes? {
+        match current_status(&vote_box, &env.block) {
+            Status::Open {} => open += Uint64::new(1),
+            Status::Closed {} | Status::Finalized {} | Status::Executed {} => {
+                closed += Uint64::new(1)
+            }
+            Status::Draft {} | Status::Cancelled {} => {}
         }
     }
     let res = VBOCResponse { open, closed };
@@ -400,44 +2234,74 @@ pub fn query_stats(deps: Deps, env: Env) -> StdResult<VoteboxStatistics> {
         total_no_count: Uint128::new(0),
         total_abstain_count: Uint128::new(0),
         total_no_veto_count: Uint128::new(0),
+        ties: Uint128::new(0),
     };
 
     for votebox in all_voteboxes {
-        stats.total_no_veto_count = stats
-            .total_no_veto_count
-            .checked_add(votebox.no_with_veto_count)?;
-
-        stats.total_abstain_count = stats
-            .total_abstain_count
-            .checked_add(votebox.abstain_count)?;
-        stats.total_no_count = stats.total_no_count.checked_add(votebox.no_count)?;
-        stats.total_yes_count = stats.total_yes_count.checked_add(votebox.yes_count)?;
+        // option totals only add up across voteboxes sharing the classic options
+        if is_classic(&votebox) && !tally_hidden(&votebox, &env.block) {
+            let counts: Vec<Uint128> = votebox.options.iter().map(|o| o.count).collect();
+            stats.total_no_count = stats.total_no_count.checked_add(counts[0])?;
+            stats.total_abstain_count = stats.total_abstain_count.checked_add(counts[1])?;
+            stats.total_yes_count = stats.total_yes_count.checked_add(counts[2])?;
+            stats.total_no_veto_count = stats.total_no_veto_count.checked_add(counts[3])?;
+        }
         stats.total_voteboxes = stats.total_voteboxes.checked_add(Uint128::new(1))?;
-        stats.total_participants = stats.total_participants.checked_add(
-            votebox.abstain_count
-                + votebox.no_count
-                + votebox.yes_count
-                + votebox.no_with_veto_count,
-        )?;
+        stats.total_participants = stats.total_participants.checked_add(votebox.voter_count)?;
 
-        if votebox.deadline.is_triggered(&env.block) {
-            stats.expired = stats.expired.checked_add(Uint128::new(1))?;
-            match get_winner(votebox) {
-                0 => stats.no_won = stats.no_won.checked_add(Uint128::new(1))?,
-                1 => stats.abstain_won = stats.abstain_won.checked_add(Uint128::new(1))?,
-                2 => stats.yes_won = stats.yes_won.checked_add(Uint128::new(1))?,
-                3 => stats.no_veto_won = stats.no_veto_won.checked_add(Uint128::new(1))?,
-                _ => {}
+        // drafts and cancelled voteboxes are neither active nor expired
+        match current_status(&votebox, &env.block) {
+            Status::Open {} => {
+                stats.active = stats.active.checked_add(Uint128::new(1))?;
+                continue;
             }
-        } else {
-            stats.active = stats.active.checked_add(Uint128::new(1))?;
+            Status::Draft {} | Status::Cancelled {} => continue,
+            Status::Closed {} | Status::Finalized {} | Status::Executed {} => {}
+        }
+        stats.expired = stats.expired.checked_add(Uint128::new(1))?;
+        // finalized voteboxes keep the outcome they recorded, others are counted the way
+        // `query_result` counts them and left out when that fails
+        let outcome = match &votebox.final_result {
+            Some(result) => result.outcome.clone(),
+            None => match tally_outcome(deps, &votebox) {
+                Ok((_, outcome, _)) => outcome,
+                Err(_) => continue,
+            },
+        };
+        let winner = match outcome {
+            Outcome::Winner { option } => Some(option),
+            // passing rules decide between the classic options too
+            Outcome::Rejected {} => Some(0),
+            Outcome::Passed {} => Some(2),
+            Outcome::RejectedWithVeto {} => Some(3),
+            Outcome::Tie { .. } => {
+                stats.ties = stats.ties.checked_add(Uint128::new(1))?;
+                None
+            }
+            Outcome::NoVotes {} | Outcome::QuorumNotMet {} => None,
+        };
+        match winner {
+            Some(option) if is_classic(&votebox) => {
+                let won = match option {
+                    0 => &mut stats.no_won,
+                    1 => &mut stats.abstain_won,
+                    2 => &mut stats.yes_won,
+                    _ => &mut stats.no_veto_won,
+                };
+                *won = won.checked_add(Uint128::new(1))?;
+            }
+            _ => {}
         }
     }
 
     Ok(stats)
 }
 
-pub fn query_voteboxes_by_owner(deps: Deps, owner: String) -> StdResult<VoteBoxListResponse> {
+pub fn query_voteboxes_by_owner(
+    deps: Deps,
+    env: Env,
+    owner: String,
+) -> StdResult<VoteBoxListResponse> {
     let voteboxes: StdResult<Vec<_>> = VOTE_BOX_LIST
         .range(deps.storage, None, None, Order::Ascending)
         .collect();
@@ -445,7 +2309,7 @@ pub fn query_voteboxes_by_owner(deps: Deps, owner: String) -> StdResult<VoteBoxL
     let mut voteboxes_owned: Vec<VoteResponse> = vec![];
     for votebox in vote_boxes {
         if votebox.owner == owner {
-            voteboxes_owned.push(votebox.into());
+            voteboxes_owned.push(vote_response(votebox, &env.block));
         }
     }
     let res = VoteBoxListResponse {
@@ -454,7 +2318,7 @@ pub fn query_voteboxes_by_owner(deps: Deps, owner: String) -> StdResult<VoteBoxL
     Ok(res)
 }
 
-pub fn query_votebox_topics(deps: Deps, topic: &String) -> StdResult<VoteBoxListResponse> {
+pub fn query_votebox_topics(deps: Deps, env: Env, topic: &str) -> StdResult<VoteBoxListResponse> {
     let voteboxes: StdResult<Vec<_>> = VOTE_BOX_LIST
         .range(deps.storage, None, None, Order::Ascending)
         .collect();
@@ -462,7 +2326,7 @@ pub fn query_votebox_topics(deps: Deps, topic: &String) -> StdResult<VoteBoxList
     let mut voteboxes_topics: Vec<VoteResponse> = vec![];
     for votebox in vote_boxes {
         if votebox.topic.to_lowercase().contains(&topic.to_lowercase()) {
-            voteboxes_topics.push(votebox.into());
+            voteboxes_topics.push(vote_response(votebox, &env.block));
         }
     }
     let res = VoteBoxListResponse {
@@ -472,6 +2336,197 @@ pub fn query_votebox_topics(deps: Deps, topic: &String) -> StdResult<VoteBoxList
 }
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use schemars::JsonSchema;
+
+    /// `Vote` as stored by version 0.1 of the contract
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    struct VoteV1 {
+        id: Uint64,
+        yes_count: Uint128,
+        no_count: Uint128,
+        abstain_count: Uint128,
+        no_with_veto_count: Uint128,
+        deadline: Scheduled,
+        owner: String,
+        topic: String,
+        description: String,
+        create_date: String,
+        total_amount: Uint128,
+        native_denom: Option<String>,
+        voters: Vec<Addr>,
+        voter_count: Uint128,
+    }
+
+    #[test]
+    fn migrate_moves_voters_and_tallies_out_of_the_votebox() {
+        let mut deps = mock_dependencies();
+        let legacy_list: Map<u64, VoteV1> = Map::new("votebox list");
+        let legacy = VoteV1 {
+            id: Uint64::new(1),
+            yes_count: Uint128::new(1),
+            no_count: Uint128::new(1),
+            abstain_count: Uint128::zero(),
+            no_with_veto_count: Uint128::zero(),
+            deadline: Scheduled::AtHeight(100_000),
+            owner: "owner".to_string(),
+            topic: "legacy".to_string(),
+            description: "description".to_string(),
+            create_date: "date".to_string(),
+            total_amount: Uint128::zero(),
+            native_denom: None,
+            voters: vec![Addr::unchecked("voter1"), Addr::unchecked("voter2")],
+            voter_count: Uint128::new(2),
+        };
+        legacy_list.save(deps.as_mut().storage, 1, &legacy).unwrap();
+        VOTE_BOX_SEQ
+            .save(deps.as_mut().storage, &Uint64::new(1))
+            .unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg { admin: None }).unwrap();
+
+        let info = cosmwasm_std::testing::mock_info("voter1", &[]);
+        let vote = |deps: DepsMut| {
+            execute_vote(
+                deps,
+                mock_env(),
+                info.clone(),
+                Uint64::new(1),
+                2,
+                None,
+                None,
+                None,
+            )
+        };
+        let err = vote(deps.as_mut()).unwrap_err();
+        assert_eq!(err, ContractError::MigrationPending {});
+        execute_migrate_voters(deps.as_mut(), Uint64::new(1), None).unwrap();
+
+        let storage = deps.as_ref().storage;
+        assert!(VOTERS.has(storage, (1, &Addr::unchecked("voter1"))));
+        assert!(VOTERS.has(storage, (1, &Addr::unchecked("voter2"))));
+        assert!(!VOTERS.has(storage, (1, &Addr::unchecked("owner"))));
+
+        let vote_box = VOTE_BOX_LIST.load(storage, 1).unwrap();
+        assert!(is_classic(&vote_box));
+        assert_eq!(vote_box.options[0].count, Uint128::new(1));
+        assert_eq!(vote_box.options[2].count, Uint128::new(1));
+        assert_eq!(vote_box.voter_count, Uint128::new(2));
+        assert_eq!(vote_box.voting_power, VotingPower::Equal {});
+
+        let legacy_voters: Map<u64, LegacyVoters> = Map::new("votebox list");
+        let legacy = legacy_voters.load(storage, 1).unwrap();
+        assert!(legacy.voters.is_empty());
+        assert_eq!(legacy.yes_count, None);
+
+        // migrated voters keep their repeat-vote protection
+        let err = vote(deps.as_mut()).unwrap_err();
+        assert_eq!(err, ContractError::VoterRepeat {});
+    }
+
+    #[test]
+    fn large_voter_lists_migrate_in_batches() {
+        let mut deps = mock_dependencies();
+        let legacy_list: Map<u64, VoteV1> = Map::new("votebox list");
+        let voters: Vec<Addr> = (0..1_200)
+            .map(|i| Addr::unchecked(format!("voter{}", i)))
+            .collect();
+        let legacy = VoteV1 {
+            id: Uint64::new(1),
+            yes_count: Uint128::new(1_200),
+            no_count: Uint128::zero(),
+            abstain_count: Uint128::zero(),
+            no_with_veto_count: Uint128::zero(),
+            deadline: Scheduled::AtHeight(100_000),
+            owner: "owner".to_string(),
+            topic: "popular".to_string(),
+            description: "description".to_string(),
+            create_date: "date".to_string(),
+            total_amount: Uint128::zero(),
+            native_denom: None,
+            voters: voters.clone(),
+            voter_count: Uint128::new(1_200),
+        };
+        legacy_list.save(deps.as_mut().storage, 1, &legacy).unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg { admin: None }).unwrap();
+        assert!(!VOTERS.has(deps.as_ref().storage, (1, &voters[0])));
+
+        // limits above the maximum are capped
+        for remaining in ["700", "200", "0"] {
+            let res = execute_migrate_voters(deps.as_mut(), Uint64::new(1), Some(1_000)).unwrap();
+            let attr = res
+                .attributes
+                .iter()
+                .find(|a| a.key == "remaining")
+                .unwrap();
+            assert_eq!(attr.value, remaining);
+        }
+        for voter in &voters {
+            assert!(VOTERS.has(deps.as_ref().storage, (1, voter)));
+        }
+        let err = execute_migrate_voters(deps.as_mut(), Uint64::new(1), None).unwrap_err();
+        assert_eq!(err, ContractError::NothingToMigrate {});
+    }
+
+    #[test]
+    fn reset_clears_voters_waiting_for_migration() {
+        let mut deps = mock_dependencies();
+        let legacy_list: Map<u64, VoteV1> = Map::new("votebox list");
+        let legacy = VoteV1 {
+            id: Uint64::new(1),
+            yes_count: Uint128::new(2),
+            no_count: Uint128::zero(),
+            abstain_count: Uint128::zero(),
+            no_with_veto_count: Uint128::zero(),
+            deadline: Scheduled::AtHeight(100_000),
+            owner: "owner".to_string(),
+            topic: "legacy".to_string(),
+            description: "description".to_string(),
+            create_date: "date".to_string(),
+            total_amount: Uint128::zero(),
+            native_denom: None,
+            voters: vec![Addr::unchecked("voter1"), Addr::unchecked("voter2")],
+            voter_count: Uint128::new(2),
+        };
+        legacy_list.save(deps.as_mut().storage, 1, &legacy).unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg { admin: None }).unwrap();
+
+        let owner = cosmwasm_std::testing::mock_info("owner", &[]);
+        reset(deps.as_mut(), mock_env(), owner.clone(), Uint64::new(1)).unwrap();
+        assert!(!PENDING_VOTERS.has(deps.as_ref().storage, 1));
+        let err = execute_migrate_voters(deps.as_mut(), Uint64::new(1), None).unwrap_err();
+        assert_eq!(err, ContractError::NothingToMigrate {});
+
+        let voter = cosmwasm_std::testing::mock_info("voter1", &[]);
+        execute_vote(
+            deps.as_mut(),
+            mock_env(),
+            voter,
+            Uint64::new(1),
+            2,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+        // a reset clears everything in one go, so it stops at a bounded number of ballots
+        for i in 0..MAX_CLEARED_ENTRIES {
+            let voter = Addr::unchecked(format!("voter{}", i + 2));
+            VOTERS
+                .save(deps.as_mut().storage, (1, &voter), &Empty {})
+                .unwrap();
+        }
+        let err = reset(deps.as_mut(), mock_env(), owner, Uint64::new(1)).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::TooManyToClear {
+                max: MAX_CLEARED_ENTRIES
+            }
+        );
+    }
 
     /*use super::*;
 
@@ -485,13 +2540,12 @@ mod tests {
     use serde::__private::de::IdentifierDeserializer;
     */
 
-
     /*
     #[test]
     fn proper_initialization() {
         ///Initialize
         let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg { admin: None };
         let info = mock_info("admin", &coins(1000, "earth"));
         let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
         let value = res.attributes;
@@ -505,7 +2559,7 @@ mod tests {
         let info = mock_info("test", &coins(1000, "earth"));
 
         ///Initialize - Create 2 and delete 1
-        let msgInit = InstantiateMsg {};
+        let msgInit = InstantiateMsg { admin: None };
         let resInit = instantiate(deps.as_mut(), mock_env(), info.clone(), msgInit).unwrap();
         let value = resInit.attributes;
         assert_eq!("0", value[1].value);
@@ -826,7 +2880,7 @@ mod tests {
         // ///Initialize create, increment and reset
         // ///Initialize
         // let mut deps = mock_dependencies_with_balance(&coins(2, "token"));
-        // let msg = InstantiateMsg {};
+        // let msg = InstantiateMsg { admin: None };
         // let info = mock_info("admin", &coins(1000, "earth"));
         // let res = instantiate(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
         // let value = res.attributes;
@@ -895,7 +2949,7 @@ mod tests {
     fn query_stats_integration() {
         let mut deps = mock_dependencies();
 
-        let msg = InstantiateMsg {};
+        let msg = InstantiateMsg { admin: None };
         let info = mock_info("creator", &[]);
         let mut env = mock_env();
         env.block.height = 1;
@@ -1,4 +1,5 @@
-use cosmwasm_std::{OverflowError, StdError};
+use crate::state::Status;
+use cosmwasm_std::{OverflowError, StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -24,20 +25,167 @@ pub enum ContractError {
     #[error("Send native tokens")]
     SendNativeTokens {},
 
-    #[error("Send native tokens")]
+    #[error("Denom not supported")]
     NotSupportDenom {},
 
+    #[error("Token not supported")]
+    NotSupportToken {},
+
+    #[error("A VoteBox can be funded with either a native denom or a cw20 token, not both")]
+    AmbiguousRewardToken {},
+
+    #[error("Reward already claimed")]
+    AlreadyClaimed {},
+
     #[error("You have already committed a vote")]
     VoterRepeat {},
 
+    #[error("You have no voting power in this VoteBox")]
+    NoVotingPower {},
+
+    #[error("Only members of the VoteBox group can vote")]
+    NotMember {},
+
+    #[error("You are not on the VoteBox allowlist")]
+    NotEligible {},
+
+    #[error("This VoteBox has no allowlist")]
+    NoAllowlist {},
+
+    #[error("This VoteBox requires a merkle proof")]
+    MissingProof {},
+
+    #[error("Invalid merkle proof")]
+    InvalidProof {},
+
+    #[error("Merkle root must be a hex encoded sha256 hash")]
+    InvalidMerkleRoot {},
+
+    #[error("No tokens locked")]
+    NothingLocked {},
+
+    #[error("Memo is longer than {max} characters")]
+    MemoTooLong { max: usize },
+
+    #[error("You have not voted in this VoteBox")]
+    NotVoted {},
+
     #[error("A VoteBox with the same topic already exists")]
     DuplicateVoteBox {},
 
     #[error("{0}")]
     Overflow(#[from] OverflowError),
 
-    #[error("Invalid Vote value - only 0,1,2 are valid")]
+    #[error("Invalid Vote value - not one of the VoteBox options")]
     InvalidVote {},
+
+    #[error("This VoteBox does not accept this kind of ballot")]
+    WrongVoteKind {},
+
+    #[error("Ranking must list distinct options of the VoteBox")]
+    InvalidRanking {},
+
+    #[error("Approve at least one option")]
+    EmptyApproval {},
+
+    #[error("An option can only be approved once")]
+    DuplicateApproval {},
+
+    #[error("Not enough voice credits, {remaining} left")]
+    InsufficientCredits { remaining: Uint128 },
+
+    #[error("Quadratic VoteBoxes need a non-zero credit budget")]
+    InvalidCredits {},
+
+    #[error("Secret ballots are only supported for single-option VoteBoxes")]
+    UnsupportedSecretBallot {},
+
+    #[error("Reveal deadline must be of the same kind as and later than the voting deadline")]
+    InvalidRevealDeadline {},
+
+    #[error("Commitment must be a hex encoded sha256 hash")]
+    InvalidCommitment {},
+
+    #[error("Revealed vote does not match the commitment")]
+    InvalidReveal {},
+
+    #[error("The reveal window is closed")]
+    RevealClosed {},
+
+    #[error("Quorum and thresholds must be between 0 and 1")]
+    InvalidThresholds {},
+
+    #[error("Thresholds need a single-choice VoteBox with the classic options")]
+    UnsupportedThresholds {},
+
+    #[error("The eligible total weight cannot be derived for this VoteBox, set total_weight")]
+    MissingTotalWeight {},
+
+    #[error("Not allowed while the VoteBox is {status:?}")]
+    InvalidStatus { status: Status },
+
+    #[error("Only VoteBoxes with passing thresholds can carry messages")]
+    MessagesNeedThresholds {},
+
+    #[error("VoteBoxes carrying messages read their total weight from the voting power")]
+    FixedTotalWeight {},
+
+    #[error("VoteBoxes carrying messages have to lock the cw20 tokens voted with")]
+    UnlockedVotingPower {},
+
+    #[error("VoteBox did not pass")]
+    NotPassed {},
+
+    #[error("A guardian needs a timelock to veto in")]
+    GuardianNeedsTimelock {},
+
+    #[error("Proposal is still timelocked")]
+    Timelocked {},
+
+    #[error("Proposal was vetoed")]
+    Vetoed {},
+
+    #[error("The veto window has closed")]
+    VetoClosed {},
+
+    #[error("Voters of this VoteBox are still being migrated")]
+    MigrationPending {},
+
+    #[error("This VoteBox has no voters left to migrate")]
+    NothingToMigrate {},
+
+    #[error("VoteBoxes with more than {max} entries kept per voter cannot be reset or removed")]
+    TooManyToClear { max: usize },
+
+    #[error("Delegating to this address would create a delegation cycle")]
+    DelegationCycle {},
+
+    #[error("You have not delegated your vote")]
+    NotDelegated {},
+
+    #[error("More than {max} delegations lead to this vote, some delegators have to undelegate")]
+    TooManyDelegators { max: usize },
+
+    #[error("Delegations lead to this vote over more than {max} hops, some delegators have to undelegate")]
+    DelegationTooDeep { max: u32 },
+
+    #[error("This VoteBox does not use the owner's casting vote")]
+    NoCastingVote {},
+
+    #[error("The casting vote must pick one of the tied options")]
+    NotTied {},
+
+    #[error("The tie has already been decided")]
+    AlreadyDecided {},
+
+    #[error("The owner can still cast the deciding vote, the result is recorded after that")]
+    AwaitingCastingVote {},
+
+    #[error("The time to cast the deciding vote is over")]
+    CastingVoteClosed {},
+
+    #[error("A VoteBox needs between {min} and {max} options with unique, non-empty labels")]
+    InvalidOptions { min: usize, max: usize },
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
@@ -5,28 +5,541 @@
 use crate::msg::{ExecuteMsg, QueryMsg};
 
  */
-use crate::state::Vote;
-
-pub fn get_winner(votebox: Vote) -> i32 {
-    let yes = votebox.yes_count;
-    let no = votebox.no_count;
-    let abs = votebox.abstain_count;
-    let veto = votebox.no_with_veto_count;
-    let mut votes_vec = vec![yes, no, abs, veto];
-    votes_vec.sort();
-    if votes_vec[0] == votes_vec[1] {
-        4
+use crate::error::ContractError;
+use crate::msg::{MerkleProof, RunoffRound};
+use crate::state::{
+    Outcome, Status, Thresholds, TieBreak, Vote, VoteOption, VotingPower, CLASSIC_OPTIONS,
+    ELIGIBLE_VOTERS,
+};
+use cosmwasm_std::{
+    Addr, BlockInfo, Decimal, Deps, Order, OverflowError, StdError, StdResult, Timestamp, Uint128,
+};
+use cw20::{BalanceResponse, Cw20QueryMsg, TokenInfoResponse};
+use cw4::Cw4Contract;
+use cw_utils::Scheduled;
+use sha2::{Digest, Sha256};
+
+/// Stage of the votebox at `block`, closing open voteboxes whose deadline has passed
+pub fn current_status(votebox: &Vote, block: &BlockInfo) -> Status {
+    match votebox.status {
+        Status::Open {} if votebox.deadline.is_triggered(block) => Status::Closed {},
+        ref status => status.clone(),
+    }
+}
+
+/// Blocks the owner has to cast the deciding vote once the votes are counted, about a day
+pub const CASTING_VOTE_BLOCKS: u64 = 14_400;
+/// Seconds the owner has to cast the deciding vote on voteboxes with a time deadline
+pub const CASTING_VOTE_SECONDS: u64 = 86_400;
+
+/// End of the owner's window to decide a tie on a `TieBreak::OwnerCastingVote` votebox.
+/// The window opens when the votes are counted, after the reveal deadline of a secret
+/// ballot.
+pub fn casting_vote_deadline(votebox: &Vote) -> Scheduled {
+    match votebox.reveal_deadline.unwrap_or(votebox.deadline) {
+        Scheduled::AtHeight(height) => {
+            Scheduled::AtHeight(height.saturating_add(CASTING_VOTE_BLOCKS))
+        }
+        Scheduled::AtTime(time) => Scheduled::AtTime(Timestamp::from_nanos(
+            time.nanos()
+                .saturating_add(CASTING_VOTE_SECONDS.saturating_mul(1_000_000_000)),
+        )),
+    }
+}
+
+/// Weight `voter` adds to a votebox, according to the box's voting power rule
+pub fn voting_power(
+    deps: Deps,
+    votebox: &Vote,
+    voter: &Addr,
+    proof: Option<&MerkleProof>,
+) -> Result<Uint128, ContractError> {
+    let weight = match &votebox.voting_power {
+        VotingPower::Equal {} => Uint128::new(1),
+        VotingPower::Cw20Balance { token, .. } => {
+            let res: BalanceResponse = deps.querier.query_wasm_smart(
+                token,
+                &Cw20QueryMsg::Balance {
+                    address: voter.to_string(),
+                },
+            )?;
+            res.balance
+        }
+        VotingPower::Staked {} => deps
+            .querier
+            .query_all_delegations(voter)?
+            .into_iter()
+            .try_fold(Uint128::zero(), |total, delegation| {
+                total.checked_add(delegation.amount.amount)
+            })?,
+        VotingPower::Cw4Group { group } => {
+            let weight = Cw4Contract::new(group.clone())
+                .is_member(&deps.querier, voter, None)?
+                .ok_or(ContractError::NotMember {})?;
+            Uint128::from(weight)
+        }
+        VotingPower::Merkle { root } => {
+            let proof = proof.ok_or(ContractError::MissingProof {})?;
+            verify_merkle_proof(root, voter, proof)?;
+            proof.weight
+        }
+    };
+    if weight.is_zero() {
+        return Err(ContractError::NoVotingPower {});
+    }
+    Ok(weight)
+}
+
+/// Checks that the `address + weight` leaf is part of the sha256 merkle tree with the given
+/// hex encoded root. Sibling hashes are combined in sorted order, as in cw20-merkle-airdrop.
+pub fn verify_merkle_proof(
+    root: &str,
+    voter: &Addr,
+    proof: &MerkleProof,
+) -> Result<(), ContractError> {
+    let leaf = format!("{}{}", voter, proof.weight);
+    let mut hash: [u8; 32] = Sha256::digest(leaf.as_bytes()).into();
+
+    for sibling in &proof.proof {
+        let mut sibling_hash = [0u8; 32];
+        hex::decode_to_slice(sibling, &mut sibling_hash)
+            .map_err(|_| ContractError::InvalidProof {})?;
+
+        let mut pair = [hash, sibling_hash];
+        pair.sort_unstable();
+        hash = Sha256::digest(&pair.concat()).into();
+    }
+
+    let mut root_hash = [0u8; 32];
+    hex::decode_to_slice(root, &mut root_hash).map_err(|_| ContractError::InvalidMerkleRoot {})?;
+    if root_hash != hash {
+        return Err(ContractError::InvalidProof {});
+    }
+    Ok(())
+}
+
+/// Total weight of everyone eligible to vote on the votebox
+pub fn eligible_weight(deps: Deps, votebox: &Vote) -> StdResult<Uint128> {
+    if let Some(total_weight) = votebox.total_weight {
+        return Ok(total_weight);
+    }
+    match &votebox.voting_power {
+        VotingPower::Cw4Group { group } => {
+            let total = Cw4Contract::new(group.clone()).total_weight(&deps.querier)?;
+            Ok(Uint128::from(total))
+        }
+        VotingPower::Cw20Balance { token, .. } => {
+            let res: TokenInfoResponse = deps
+                .querier
+                .query_wasm_smart(token, &Cw20QueryMsg::TokenInfo {})?;
+            Ok(res.total_supply)
+        }
+        VotingPower::Equal {} if votebox.allowlist => {
+            let eligible = ELIGIBLE_VOTERS
+                .prefix(votebox.id.u64())
+                .keys(deps.storage, None, None, Order::Ascending)
+                .count();
+            Ok(Uint128::from(eligible as u128))
+        }
+        _ => Err(StdError::generic_err("Eligible total weight is unknown")),
+    }
+}
+
+/// Outcome of a classic votebox under x/gov style passing rules. `counts` are the no,
+/// abstain, yes and no_with_veto tallies.
+pub fn passing_outcome(
+    counts: [Uint128; 4],
+    total_weight: Uint128,
+    thresholds: &Thresholds,
+) -> StdResult<Outcome> {
+    let [no, abstain, yes, veto] = counts;
+    let voted = no
+        .checked_add(abstain)?
+        .checked_add(yes)?
+        .checked_add(veto)?;
+    if voted.is_zero() || total_weight.is_zero() {
+        return Ok(Outcome::QuorumNotMet {});
+    }
+    // a quorum is at most one, so a turnout at or above the total weight always meets it,
+    // and its ratio might not fit a `Decimal`
+    if voted < total_weight && Decimal::from_ratio(voted, total_weight) < thresholds.quorum {
+        return Ok(Outcome::QuorumNotMet {});
+    }
+    if Decimal::from_ratio(veto, voted) > thresholds.veto_threshold {
+        return Ok(Outcome::RejectedWithVeto {});
+    }
+    let non_abstain = voted.checked_sub(abstain)?;
+    if !non_abstain.is_zero() && Decimal::from_ratio(yes, non_abstain) > thresholds.threshold {
+        Ok(Outcome::Passed {})
     } else {
-        if votes_vec[0] == votebox.yes_count {
-            return 2;
+        Ok(Outcome::Rejected {})
+    }
+}
+
+/// Instant-runoff count over `(ranking, weight)` ballots for `options` options.
+///
+/// Each round counts every ballot for its most preferred option still in the race. An
+/// option holding more than half of the counted weight wins, otherwise the lowest option is
+/// eliminated. Options tied for the lowest tally go together only when their combined tally
+/// is still below every other option, so no transfer between them could have saved one.
+/// Otherwise the tie eliminates the option with fewer votes in the previous round, then the
+/// one with the higher index. When every option left is tied, the count ends in a tie
+/// between them.
+pub fn instant_runoff(
+    options: usize,
+    ballots: &[(Vec<u32>, Uint128)],
+) -> Result<(Vec<RunoffRound>, Outcome), OverflowError> {
+    let mut eliminated = vec![false; options];
+    let mut rounds = vec![];
+    loop {
+        let mut tallies = vec![Uint128::zero(); options];
+        let mut exhausted = Uint128::zero();
+        for (ranking, weight) in ballots {
+            match ranking.iter().find(|option| !eliminated[**option as usize]) {
+                Some(option) => {
+                    let tally = &mut tallies[*option as usize];
+                    *tally = tally.checked_add(*weight)?;
+                }
+                None => exhausted = exhausted.checked_add(*weight)?,
+            }
         }
-        if votes_vec[0] == votebox.no_count {
-            return 0;
+        let counted = tallies
+            .iter()
+            .try_fold(Uint128::zero(), |total, tally| total.checked_add(*tally))?;
+        let remaining: Vec<u32> = (0..options as u32)
+            .filter(|option| !eliminated[*option as usize])
+            .collect();
+
+        // tallies never exceed the counted total, so the subtraction cannot underflow
+        let majority = remaining.iter().find(|option| {
+            let tally = tallies[**option as usize];
+            tally > counted - tally
+        });
+        let lowest = remaining
+            .iter()
+            .map(|option| tallies[*option as usize])
+            .min()
+            .unwrap_or_default();
+        let mut losers: Vec<u32> = remaining
+            .iter()
+            .copied()
+            .filter(|option| tallies[*option as usize] == lowest)
+            .collect();
+
+        if let Some(winner) = majority {
+            let option = *winner;
+            rounds.push(RunoffRound {
+                tallies,
+                exhausted,
+                eliminated: vec![],
+            });
+            return Ok((rounds, Outcome::Winner { option }));
         }
-        return if votes_vec[0] == votebox.abstain_count {
-            1
-        } else {
-            3
+        if losers.len() == remaining.len() {
+            rounds.push(RunoffRound {
+                tallies,
+                exhausted,
+                eliminated: vec![],
+            });
+            let outcome = if counted.is_zero() {
+                Outcome::NoVotes {}
+            } else {
+                Outcome::Tie { options: losers }
+            };
+            return Ok((rounds, outcome));
+        }
+        let combined = losers.iter().try_fold(Uint128::zero(), |total, option| {
+            total.checked_add(tallies[*option as usize])
+        })?;
+        let next_lowest = remaining
+            .iter()
+            .map(|option| tallies[*option as usize])
+            .filter(|tally| *tally > lowest)
+            .min();
+        let together = matches!(next_lowest, Some(next) if combined < next);
+        if losers.len() > 1 && !together {
+            let previous = rounds.last().map(|round: &RunoffRound| &round.tallies);
+            let loser = losers
+                .iter()
+                .copied()
+                .min_by_key(|option| {
+                    let votes = previous.map_or(Uint128::zero(), |t| t[*option as usize]);
+                    (votes, std::cmp::Reverse(*option))
+                })
+                .unwrap_or_default();
+            losers = vec![loser];
+        }
+        for option in &losers {
+            eliminated[*option as usize] = true;
+        }
+        rounds.push(RunoffRound {
+            tallies,
+            exhausted,
+            eliminated: losers,
+        });
+    }
+}
+
+/// Options of a votebox created without custom labels
+pub fn classic_options() -> Vec<VoteOption> {
+    CLASSIC_OPTIONS
+        .iter()
+        .map(|label| VoteOption {
+            label: label.to_string(),
+            count: Uint128::zero(),
+            reached_at: 0,
+        })
+        .collect()
+}
+
+/// Whether the votebox uses the classic no / abstain / yes / no_with_veto options
+pub fn is_classic(votebox: &Vote) -> bool {
+    votebox.options.len() == CLASSIC_OPTIONS.len()
+        && votebox
+            .options
+            .iter()
+            .zip(CLASSIC_OPTIONS.iter())
+            .all(|(option, label)| option.label == *label)
+}
+
+/// Option with the most weight, or the options tied for it
+pub fn get_winner(tallies: &[Uint128]) -> Outcome {
+    let top = tallies.iter().max().copied().unwrap_or_default();
+    if top.is_zero() {
+        return Outcome::NoVotes {};
+    }
+    let leaders: Vec<u32> = (0..tallies.len() as u32)
+        .filter(|option| tallies[*option as usize] == top)
+        .collect();
+    match leaders[..] {
+        [option] => Outcome::Winner { option },
+        _ => Outcome::Tie { options: leaders },
+    }
+}
+
+/// Settles a tie between the top options with the votebox's tie-break policy, returning
+/// the policy when it decided the outcome
+pub fn break_tie(votebox: &Vote, outcome: Outcome) -> (Outcome, Option<TieBreak>) {
+    let options = match outcome {
+        Outcome::Tie { options } => options,
+        other => return (other, None),
+    };
+    let winner = match &votebox.tie_break {
+        TieBreak::StatusQuo {} => {
+            return (Outcome::Tie { options }, Some(TieBreak::StatusQuo {}));
+        }
+        TieBreak::EarliestToReach {} => options
+            .iter()
+            .copied()
+            .min_by_key(|option| votebox.options[*option as usize].reached_at),
+        // the tie stays open until the owner has cast its vote
+        TieBreak::OwnerCastingVote {} => votebox.casting_vote.filter(|o| options.contains(o)),
+        TieBreak::Random {} => {
+            let seed = format!(
+                "{}:{}:{}",
+                votebox.id, votebox.last_tally_height, votebox.tally_updates
+            );
+            let hash = Sha256::digest(seed.as_bytes());
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&hash[..8]);
+            let pick = u64::from_be_bytes(bytes) % options.len() as u64;
+            options.get(pick as usize).copied()
+        }
+    };
+    match winner {
+        Some(option) => (Outcome::Winner { option }, Some(votebox.tie_break.clone())),
+        None => (Outcome::Tie { options }, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ballots(ballots: &[(&[u32], u128)]) -> Vec<(Vec<u32>, Uint128)> {
+        ballots
+            .iter()
+            .map(|(ranking, weight)| (ranking.to_vec(), Uint128::new(*weight)))
+            .collect()
+    }
+
+    fn tallies(tallies: &[u128]) -> Vec<Uint128> {
+        tallies.iter().map(|tally| Uint128::new(*tally)).collect()
+    }
+
+    #[test]
+    fn first_round_majority_wins() {
+        let (rounds, outcome) =
+            instant_runoff(3, &ballots(&[(&[0, 1], 3), (&[1], 1), (&[2, 0], 1)])).unwrap();
+        assert_eq!(outcome, Outcome::Winner { option: 0 });
+        assert_eq!(rounds.len(), 1);
+        assert_eq!(rounds[0].tallies, tallies(&[3, 1, 1]));
+        assert!(rounds[0].eliminated.is_empty());
+    }
+
+    #[test]
+    fn eliminated_options_transfer_to_next_preference() {
+        let (rounds, outcome) = instant_runoff(
+            3,
+            &ballots(&[(&[0], 4), (&[1, 0], 2), (&[1, 2], 2), (&[2, 1], 3)]),
+        )
+        .unwrap();
+        assert_eq!(outcome, Outcome::Winner { option: 1 });
+        assert_eq!(rounds.len(), 2);
+        assert_eq!(rounds[0].tallies, tallies(&[4, 4, 3]));
+        assert_eq!(rounds[0].eliminated, vec![2]);
+        assert_eq!(rounds[1].tallies, tallies(&[4, 7, 0]));
+    }
+
+    #[test]
+    fn exhausted_ballots_stop_counting() {
+        let (rounds, outcome) = instant_runoff(
+            4,
+            &ballots(&[(&[0], 4), (&[1], 3), (&[2], 1), (&[3, 2], 1)]),
+        )
+        .unwrap();
+        assert_eq!(rounds[0].tallies, tallies(&[4, 3, 1, 1]));
+        assert_eq!(rounds[0].eliminated, vec![2, 3]);
+        // both ballots for the eliminated options have no preference left
+        assert_eq!(outcome, Outcome::Winner { option: 0 });
+        assert_eq!(rounds[1].tallies, tallies(&[4, 3, 0, 0]));
+        assert_eq!(rounds[1].exhausted, Uint128::new(2));
+    }
+
+    #[test]
+    fn tied_options_that_could_win_are_eliminated_one_at_a_time() {
+        // 1 and 2 together outweigh 0, and 2's voters prefer 1 over 0
+        let (rounds, outcome) =
+            instant_runoff(3, &ballots(&[(&[0], 4), (&[1], 3), (&[2, 1], 3)])).unwrap();
+        assert_eq!(rounds[0].eliminated, vec![2]);
+        assert_eq!(rounds[1].tallies, tallies(&[4, 6, 0]));
+        assert_eq!(outcome, Outcome::Winner { option: 1 });
+    }
+
+    #[test]
+    fn ties_eliminate_the_option_weaker_in_the_previous_round() {
+        let (rounds, outcome) = instant_runoff(
+            4,
+            &ballots(&[(&[0], 6), (&[1], 2), (&[2], 3), (&[3, 1], 1)]),
+        )
+        .unwrap();
+        assert_eq!(rounds[0].eliminated, vec![3]);
+        assert_eq!(rounds[1].tallies, tallies(&[6, 3, 3, 0]));
+        assert_eq!(rounds[1].eliminated, vec![1]);
+        assert_eq!(outcome, Outcome::Winner { option: 0 });
+    }
+
+    #[test]
+    fn tied_lowest_options_are_eliminated_together() {
+        let (rounds, outcome) = instant_runoff(
+            4,
+            &ballots(&[(&[0], 5), (&[1, 3], 4), (&[2, 3], 1), (&[3, 1], 1)]),
+        )
+        .unwrap();
+        assert_eq!(rounds[0].eliminated, vec![2, 3]);
+        assert_eq!(rounds[1].tallies, tallies(&[5, 5, 0, 0]));
+        assert_eq!(rounds[1].exhausted, Uint128::new(1));
+        assert_eq!(
+            outcome,
+            Outcome::Tie {
+                options: vec![0, 1]
+            }
+        );
+        assert!(rounds[1].eliminated.is_empty());
+    }
+
+    #[test]
+    fn plurality_winners() {
+        let cases: [(&[u128], Outcome); 7] = [
+            (&[0, 0, 5, 0], Outcome::Winner { option: 2 }),
+            (&[3, 1, 2, 0], Outcome::Winner { option: 0 }),
+            (&[1, 2, 1, 1], Outcome::Winner { option: 1 }),
+            (&[0, 0, 1, 7], Outcome::Winner { option: 3 }),
+            (
+                &[4, 0, 4, 1],
+                Outcome::Tie {
+                    options: vec![0, 2],
+                },
+            ),
+            (
+                &[2, 2, 2],
+                Outcome::Tie {
+                    options: vec![0, 1, 2],
+                },
+            ),
+            (&[0, 0, 0, 0], Outcome::NoVotes {}),
+        ];
+        for (counts, expected) in cases.iter() {
+            let tallies: Vec<Uint128> = counts.iter().map(|count| Uint128::new(*count)).collect();
+            assert_eq!(&get_winner(&tallies), expected, "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn passing_outcomes() {
+        let thresholds = Thresholds {
+            quorum: Decimal::percent(40),
+            threshold: Decimal::percent(50),
+            veto_threshold: Decimal::percent(33),
+        };
+        // no, abstain, yes, veto, eligible total
+        let cases: [([u128; 4], u128, Outcome); 8] = [
+            ([1, 0, 2, 0], 10, Outcome::QuorumNotMet {}),
+            ([0, 0, 0, 0], 10, Outcome::QuorumNotMet {}),
+            ([0, 0, 4, 0], 0, Outcome::QuorumNotMet {}),
+            ([1, 0, 3, 0], 10, Outcome::Passed {}),
+            ([2, 0, 2, 0], 10, Outcome::Rejected {}),
+            // abstaining counts for the quorum but not for the threshold
+            ([1, 6, 2, 0], 10, Outcome::Passed {}),
+            ([0, 4, 0, 0], 10, Outcome::Rejected {}),
+            ([0, 0, 6, 3], 10, Outcome::RejectedWithVeto {}),
+        ];
+        for (counts, total, expected) in cases.iter() {
+            let counts = [
+                Uint128::new(counts[0]),
+                Uint128::new(counts[1]),
+                Uint128::new(counts[2]),
+                Uint128::new(counts[3]),
+            ];
+            let outcome = passing_outcome(counts, Uint128::new(*total), &thresholds).unwrap();
+            assert_eq!(&outcome, expected, "{:?} of {}", counts, total);
+        }
+    }
+
+    #[test]
+    fn passing_outcome_overflow_is_an_error() {
+        let thresholds = Thresholds {
+            quorum: Decimal::percent(40),
+            threshold: Decimal::percent(50),
+            veto_threshold: Decimal::permille(334),
         };
+        let counts = [
+            Uint128::MAX,
+            Uint128::zero(),
+            Uint128::new(1),
+            Uint128::zero(),
+        ];
+        assert!(passing_outcome(counts, Uint128::MAX, &thresholds).is_err());
+    }
+
+    #[test]
+    fn turnout_above_the_total_weight_meets_the_quorum() {
+        let thresholds = Thresholds {
+            quorum: Decimal::percent(40),
+            threshold: Decimal::percent(50),
+            veto_threshold: Decimal::permille(334),
+        };
+        let yes = Uint128::new(1_000_000_000_000_000_000_000);
+        let counts = [Uint128::zero(), Uint128::zero(), yes, Uint128::zero()];
+        let outcome = passing_outcome(counts, Uint128::new(1), &thresholds).unwrap();
+        assert_eq!(outcome, Outcome::Passed {});
+    }
+
+    #[test]
+    fn no_ballots_has_no_winner() {
+        let (rounds, outcome) = instant_runoff(2, &[]).unwrap();
+        assert_eq!(outcome, Outcome::NoVotes {});
+        assert_eq!(rounds.len(), 1);
+        assert_eq!(rounds[0].tallies, tallies(&[0, 0]));
     }
 }
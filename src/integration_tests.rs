@@ -1,71 +1,2934 @@
-// #[cfg(test)]
-// mod tests {
-//     use crate::helpers::CwTemplateContract;
-//     use crate::msg::InstantiateMsg;
-//     use cosmwasm_std::{Addr, Coin, Empty, Uint128};
-//     use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
-//
-//     pub fn contract_template() -> Box<dyn Contract<Empty>> {
-//         let contract = ContractWrapper::new(
-//             crate::contract::execute,
-//             crate::contract::instantiate,
-//             crate::contract::query,
-//         );
-//         Box::new(contract)
-//     }
-//
-//     const USER: &str = "USER";
-//     const ADMIN: &str = "ADMIN";
-//     const NATIVE_DENOM: &str = "denom";
-//
-//     fn mock_app() -> App {
-//         AppBuilder::new().build(|router, _, storage| {
-//             router
-//                 .bank
-//                 .init_balance(
-//                     storage,
-//                     &Addr::unchecked(USER),
-//                     vec![Coin {
-//                         denom: NATIVE_DENOM.to_string(),
-//                         amount: Uint128::new(1),
-//                     }],
-//                 )
-//                 .unwrap();
-//         })
-//     }
-//
-//     fn proper_instantiate() -> (App, CwTemplateContract) {
-//         let mut app = mock_app();
-//         let cw_template_id = app.store_code(contract_template());
-//
-//         let msg = InstantiateMsg { count: 1i32 };
-//         let cw_template_contract_addr = app
-//             .instantiate_contract(
-//                 cw_template_id,
-//                 Addr::unchecked(ADMIN),
-//                 &msg,
-//                 &[],
-//                 "test",
-//                 None,
-//             )
-//             .unwrap();
-//
-//         let cw_template_contract = CwTemplateContract(cw_template_contract_addr);
-//
-//         (app, cw_template_contract)
-//     }
-//
-//     mod count {
-//         use super::*;
-//         use crate::msg::ExecuteMsg;
-//
-//         #[test]
-//         fn count() {
-//             let (mut app, cw_template_contract) = proper_instantiate();
-//
-//             let msg = ExecuteMsg::Increment {};
-//             let cosmos_msg = cw_template_contract.call(msg).unwrap();
-//             app.execute(Addr::unchecked(USER), cosmos_msg).unwrap();
-//         }
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use crate::msg::{
+        ClaimStatusResponse, ExecuteMsg, InstantiateMsg, QueryMsg, ReceiveMsg, ResultResponse,
+        VoteResponse,
+    };
+    use crate::state::{Outcome, Status, Thresholds, TieBreak, VoteKind, VotingPower};
+    use crate::ContractError;
+    use cosmwasm_std::{coins, Addr, CosmosMsg, Decimal, Empty, Uint128, Uint64};
+    use cw20::{BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg};
+    use cw_multi_test::{App, AppResponse, Contract, ContractWrapper, Executor};
+    use cw_utils::{Duration, Scheduled};
+
+    pub fn contract_vote() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::contract::execute,
+            crate::contract::instantiate,
+            crate::contract::query,
+        );
+        Box::new(contract)
+    }
+
+    const OWNER: &str = "owner";
+    const VOTER1: &str = "voter1";
+    const VOTER2: &str = "voter2";
+    const STRANGER: &str = "stranger";
+    const ADMIN: &str = "admin";
+    const NATIVE_DENOM: &str = "ujuno";
+    const DEADLINE: u64 = 12_400;
+
+    fn mock_app() -> App {
+        App::new(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &Addr::unchecked(OWNER), coins(1_000, NATIVE_DENOM))
+                .unwrap();
+        })
+    }
+
+    pub fn contract_cw20() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            cw20_base::contract::execute,
+            cw20_base::contract::instantiate,
+            cw20_base::contract::query,
+        );
+        Box::new(contract)
+    }
+
+    fn instantiate_cw20(app: &mut App, balances: &[(&str, u128)]) -> Addr {
+        let cw20_id = app.store_code(contract_cw20());
+        let msg = cw20_base::msg::InstantiateMsg {
+            name: "Vote Token".to_string(),
+            symbol: "VOTE".to_string(),
+            decimals: 6,
+            initial_balances: balances
+                .iter()
+                .map(|(address, amount)| Cw20Coin {
+                    address: address.to_string(),
+                    amount: Uint128::new(*amount),
+                })
+                .collect(),
+            mint: None,
+            marketing: None,
+        };
+        app.instantiate_contract(
+            cw20_id,
+            Addr::unchecked(ADMIN),
+            &msg,
+            &[],
+            "vote token",
+            None,
+        )
+        .unwrap()
+    }
+
+    fn cw20_balance(app: &App, token: &Addr, address: &str) -> Uint128 {
+        let res: BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(
+                token,
+                &Cw20QueryMsg::Balance {
+                    address: address.to_string(),
+                },
+            )
+            .unwrap();
+        res.balance
+    }
+
+    fn proper_instantiate() -> (App, Addr) {
+        let mut app = mock_app();
+        let vote_id = app.store_code(contract_vote());
+
+        let vote_addr = app
+            .instantiate_contract(
+                vote_id,
+                Addr::unchecked(ADMIN),
+                &InstantiateMsg {
+                    admin: Some(ADMIN.to_string()),
+                },
+                &[],
+                "votebox",
+                None,
+            )
+            .unwrap();
+
+        (app, vote_addr)
+    }
+
+    /// Optional `create_vote_box` fields, everything not set is left out of the message
+    #[derive(Default)]
+    struct BoxConfig {
+        native_denom: Option<String>,
+        cw20_addr: Option<String>,
+        voting_power: Option<VotingPower>,
+        allowlist: Option<Vec<String>>,
+        options: Option<Vec<String>>,
+        kind: Option<VoteKind>,
+        reveal_deadline: Option<Scheduled>,
+        hide_tally_until_end: Option<bool>,
+        thresholds: Option<Thresholds>,
+        total_weight: Option<Uint128>,
+        tie_break: Option<TieBreak>,
+        draft: Option<bool>,
+        msgs: Option<Vec<CosmosMsg>>,
+        timelock: Option<Duration>,
+        guardian: Option<String>,
+    }
+
+    fn try_create_box(
+        app: &mut App,
+        vote_addr: &Addr,
+        topic: &str,
+        config: BoxConfig,
+    ) -> Result<AppResponse, ContractError> {
+        try_create_box_as(app, vote_addr, OWNER, topic, config)
+    }
+
+    fn create_box_msg(topic: &str, config: BoxConfig) -> ExecuteMsg {
+        ExecuteMsg::create_vote_box {
+            deadline: Scheduled::AtHeight(DEADLINE),
+            owner: OWNER.to_string(),
+            topic: topic.to_string(),
+            description: "description".to_string(),
+            create_date: "date".to_string(),
+            native_denom: config.native_denom,
+            cw20_addr: config.cw20_addr,
+            voting_power: config.voting_power,
+            allowlist: config.allowlist,
+            options: config.options,
+            kind: config.kind,
+            reveal_deadline: config.reveal_deadline,
+            hide_tally_until_end: config.hide_tally_until_end,
+            thresholds: config.thresholds,
+            total_weight: config.total_weight,
+            tie_break: config.tie_break,
+            draft: config.draft,
+            msgs: config.msgs,
+            timelock: config.timelock,
+            guardian: config.guardian,
+        }
+    }
+
+    fn try_create_box_as(
+        app: &mut App,
+        vote_addr: &Addr,
+        sender: &str,
+        topic: &str,
+        config: BoxConfig,
+    ) -> Result<AppResponse, ContractError> {
+        let msg = create_box_msg(topic, config);
+        app.execute_contract(Addr::unchecked(sender), vote_addr.clone(), &msg, &[])
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    fn create_box(app: &mut App, vote_addr: &Addr, topic: &str, native_denom: Option<&str>) {
+        let config = BoxConfig {
+            native_denom: native_denom.map(String::from),
+            ..BoxConfig::default()
+        };
+        try_create_box(app, vote_addr, topic, config).unwrap();
+    }
+
+    fn vote_msg(vote_type: i32) -> ExecuteMsg {
+        ExecuteMsg::vote {
+            id: Uint64::new(1),
+            vote_type,
+            proof: None,
+            memo: None,
+            votes: None,
+        }
+    }
+
+    fn try_vote(
+        app: &mut App,
+        vote_addr: &Addr,
+        voter: &str,
+        vote_type: i32,
+    ) -> Result<AppResponse, ContractError> {
+        let msg = vote_msg(vote_type);
+        app.execute_contract(Addr::unchecked(voter), vote_addr.clone(), &msg, &[])
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    fn vote(app: &mut App, vote_addr: &Addr, voter: &str, vote_type: i32) -> AppResponse {
+        try_vote(app, vote_addr, voter, vote_type).unwrap()
+    }
+
+    fn try_execute(
+        app: &mut App,
+        vote_addr: &Addr,
+        sender: &str,
+        msg: ExecuteMsg,
+    ) -> Result<AppResponse, ContractError> {
+        app.execute_contract(Addr::unchecked(sender), vote_addr.clone(), &msg, &[])
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    fn try_finalize(app: &mut App, vote_addr: &Addr) -> Result<AppResponse, ContractError> {
+        let msg = ExecuteMsg::finalize { id: Uint64::new(1) };
+        try_execute(app, vote_addr, STRANGER, msg)
+    }
+
+    fn query_box(app: &App, vote_addr: &Addr, id: u64) -> VoteResponse {
+        let msg = QueryMsg::query_vote {
+            id: Uint64::new(id),
+        };
+        app.wrap().query_wasm_smart(vote_addr, &msg).unwrap()
+    }
+
+    /// Tally of the option labelled `label`
+    fn count(res: &VoteResponse, label: &str) -> Uint128 {
+        res.options
+            .iter()
+            .find(|option| option.label == label)
+            .unwrap()
+            .count
+    }
+
+    /// Cosmos Hub style passing rules
+    fn gov_thresholds() -> Thresholds {
+        Thresholds {
+            quorum: Decimal::percent(40),
+            threshold: Decimal::percent(50),
+            veto_threshold: Decimal::permille(334),
+        }
+    }
+
+    fn query_result(app: &App, vote_addr: &Addr) -> cosmwasm_std::StdResult<ResultResponse> {
+        let msg = QueryMsg::query_result { id: Uint64::new(1) };
+        app.wrap().query_wasm_smart(vote_addr, &msg)
+    }
+
+    fn claim_status(app: &App, vote_addr: &Addr, address: &str) -> ClaimStatusResponse {
+        let msg = QueryMsg::query_claim_status {
+            id: Uint64::new(1),
+            address: address.to_string(),
+        };
+        app.wrap().query_wasm_smart(vote_addr, &msg).unwrap()
+    }
+
+    fn end_voting(app: &mut App) {
+        app.update_block(|block| block.height = DEADLINE + 1);
+    }
+
+    fn balance(app: &App, addr: &str) -> Uint128 {
+        app.wrap().query_balance(addr, NATIVE_DENOM).unwrap().amount
+    }
+
+    mod native_rewards {
+        use super::*;
+
+        #[test]
+        fn deposit_then_claim_pays_each_voter() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "paid poll", Some(NATIVE_DENOM));
+
+            let msg = ExecuteMsg::deposit { id: Uint64::new(1) };
+            app.execute_contract(
+                Addr::unchecked(OWNER),
+                vote_addr.clone(),
+                &msg,
+                &coins(600, NATIVE_DENOM),
+            )
+            .unwrap();
+
+            let res: VoteResponse = app
+                .wrap()
+                .query_wasm_smart(&vote_addr, &QueryMsg::query_vote { id: Uint64::new(1) })
+                .unwrap();
+            assert_eq!(res.total_amount, Uint128::new(600));
+            assert_eq!(balance(&app, vote_addr.as_str()), Uint128::new(600));
+
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 0);
+
+            let claim = ExecuteMsg::claim { id: Uint64::new(1) };
+            let err = app
+                .execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &claim, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::Unexpired {}, err.downcast().unwrap());
+
+            end_voting(&mut app);
+
+            app.execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &claim, &[])
+                .unwrap();
+            assert_eq!(balance(&app, VOTER1), Uint128::new(300));
+
+            let err = app
+                .execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &claim, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::AlreadyClaimed {}, err.downcast().unwrap());
+
+            let err = app
+                .execute_contract(Addr::unchecked(STRANGER), vote_addr.clone(), &claim, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+            app.execute_contract(Addr::unchecked(VOTER2), vote_addr.clone(), &claim, &[])
+                .unwrap();
+            assert_eq!(balance(&app, VOTER2), Uint128::new(300));
+            assert_eq!(balance(&app, vote_addr.as_str()), Uint128::zero());
+        }
+
+        #[test]
+        fn deposit_validates_funds() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "paid poll", Some(NATIVE_DENOM));
+            create_box(&mut app, &vote_addr, "free poll", None);
+            app.sudo(cw_multi_test::SudoMsg::Bank(
+                cw_multi_test::BankSudo::Mint {
+                    to_address: OWNER.to_string(),
+                    amount: coins(100, "uatom"),
+                },
+            ))
+            .unwrap();
+
+            let msg = ExecuteMsg::deposit { id: Uint64::new(1) };
+            let err = app
+                .execute_contract(Addr::unchecked(OWNER), vote_addr.clone(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::SendNativeTokens {}, err.downcast().unwrap());
+
+            let err = app
+                .execute_contract(
+                    Addr::unchecked(OWNER),
+                    vote_addr.clone(),
+                    &msg,
+                    &coins(100, "uatom"),
+                )
+                .unwrap_err();
+            assert_eq!(ContractError::NotSupportDenom {}, err.downcast().unwrap());
+
+            let err = app
+                .execute_contract(
+                    Addr::unchecked(OWNER),
+                    vote_addr.clone(),
+                    &msg,
+                    &[
+                        cosmwasm_std::coin(100, "uatom"),
+                        cosmwasm_std::coin(100, NATIVE_DENOM),
+                    ],
+                )
+                .unwrap_err();
+            assert_eq!(ContractError::NotSupportDenom {}, err.downcast().unwrap());
+
+            let free = ExecuteMsg::deposit { id: Uint64::new(2) };
+            let err = app
+                .execute_contract(
+                    Addr::unchecked(OWNER),
+                    vote_addr.clone(),
+                    &free,
+                    &coins(100, NATIVE_DENOM),
+                )
+                .unwrap_err();
+            assert_eq!(ContractError::SendNativeTokens {}, err.downcast().unwrap());
+
+            end_voting(&mut app);
+            let err = app
+                .execute_contract(
+                    Addr::unchecked(OWNER),
+                    vote_addr.clone(),
+                    &msg,
+                    &coins(100, NATIVE_DENOM),
+                )
+                .unwrap_err();
+            assert_eq!(ContractError::Expired {}, err.downcast().unwrap());
+        }
+
+        #[test]
+        fn payout_is_fixed_for_every_voter() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "paid poll", Some(NATIVE_DENOM));
+
+            let msg = ExecuteMsg::deposit { id: Uint64::new(1) };
+            app.execute_contract(
+                Addr::unchecked(OWNER),
+                vote_addr.clone(),
+                &msg,
+                &coins(1_000, NATIVE_DENOM),
+            )
+            .unwrap();
+
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            vote(&mut app, &vote_addr, STRANGER, 0);
+            end_voting(&mut app);
+
+            let claim = ExecuteMsg::claim { id: Uint64::new(1) };
+            for voter in [VOTER1, VOTER2, STRANGER] {
+                app.execute_contract(Addr::unchecked(voter), vote_addr.clone(), &claim, &[])
+                    .unwrap();
+                assert_eq!(balance(&app, voter), Uint128::new(333));
+            }
+            // integer division leaves the remainder in the contract
+            assert_eq!(balance(&app, vote_addr.as_str()), Uint128::new(1));
+
+            let status = claim_status(&app, &vote_addr, VOTER2);
+            assert_eq!(
+                status,
+                ClaimStatusResponse {
+                    voted: true,
+                    claimed: true,
+                    amount: Uint128::new(333),
+                }
+            );
+        }
+
+        #[test]
+        fn claim_status_tracks_voters() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "paid poll", Some(NATIVE_DENOM));
+
+            let msg = ExecuteMsg::deposit { id: Uint64::new(1) };
+            app.execute_contract(
+                Addr::unchecked(OWNER),
+                vote_addr.clone(),
+                &msg,
+                &coins(500, NATIVE_DENOM),
+            )
+            .unwrap();
+            vote(&mut app, &vote_addr, VOTER1, 1);
+            vote(&mut app, &vote_addr, VOTER2, 3);
+
+            let status = claim_status(&app, &vote_addr, STRANGER);
+            assert!(!status.voted);
+            assert_eq!(status.amount, Uint128::zero());
+
+            let status = claim_status(&app, &vote_addr, VOTER1);
+            assert_eq!(
+                status,
+                ClaimStatusResponse {
+                    voted: true,
+                    claimed: false,
+                    amount: Uint128::new(250),
+                }
+            );
+
+            end_voting(&mut app);
+            let claim = ExecuteMsg::claim { id: Uint64::new(1) };
+            app.execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &claim, &[])
+                .unwrap();
+
+            assert!(claim_status(&app, &vote_addr, VOTER1).claimed);
+            assert!(!claim_status(&app, &vote_addr, VOTER2).claimed);
+        }
+
+        #[test]
+        fn claim_on_free_votebox_fails() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "free poll", None);
+            vote(&mut app, &vote_addr, VOTER1, 1);
+            end_voting(&mut app);
+
+            let claim = ExecuteMsg::claim { id: Uint64::new(1) };
+            let err = app
+                .execute_contract(Addr::unchecked(VOTER1), vote_addr, &claim, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::FreeVotes {}, err.downcast().unwrap());
+        }
+    }
+
+    mod cw20_rewards {
+        use super::*;
+        use cosmwasm_std::to_binary;
+
+        fn create_cw20_box(app: &mut App, vote_addr: &Addr, token: &Addr) {
+            let config = BoxConfig {
+                cw20_addr: Some(token.to_string()),
+                ..BoxConfig::default()
+            };
+            try_create_box(app, vote_addr, "token poll", config).unwrap();
+        }
+
+        fn send_deposit(
+            app: &mut App,
+            token: &Addr,
+            vote_addr: &Addr,
+            sender: &str,
+            amount: u128,
+        ) -> Result<AppResponse, ContractError> {
+            let msg = Cw20ExecuteMsg::Send {
+                contract: vote_addr.to_string(),
+                amount: Uint128::new(amount),
+                msg: to_binary(&ReceiveMsg::deposit { id: Uint64::new(1) }).unwrap(),
+            };
+            app.execute_contract(Addr::unchecked(sender), token.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        #[test]
+        fn deposit_via_send_then_claim_transfers_tokens() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let token = instantiate_cw20(&mut app, &[(OWNER, 1_000), (STRANGER, 1_000)]);
+            create_cw20_box(&mut app, &vote_addr, &token);
+
+            send_deposit(&mut app, &token, &vote_addr, OWNER, 400).unwrap();
+            let res: VoteResponse = app
+                .wrap()
+                .query_wasm_smart(&vote_addr, &QueryMsg::query_vote { id: Uint64::new(1) })
+                .unwrap();
+            assert_eq!(res.total_amount, Uint128::new(400));
+            assert_eq!(res.cw20_addr, Some(token.clone()));
+            assert_eq!(
+                cw20_balance(&app, &token, vote_addr.as_str()),
+                Uint128::new(400)
+            );
+
+            let err = send_deposit(&mut app, &token, &vote_addr, STRANGER, 100).unwrap_err();
+            assert_eq!(ContractError::Unauthorized {}, err);
+
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 1);
+            end_voting(&mut app);
+
+            let claim = ExecuteMsg::claim { id: Uint64::new(1) };
+            app.execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &claim, &[])
+                .unwrap();
+            app.execute_contract(Addr::unchecked(VOTER2), vote_addr.clone(), &claim, &[])
+                .unwrap();
+            assert_eq!(cw20_balance(&app, &token, VOTER1), Uint128::new(200));
+            assert_eq!(cw20_balance(&app, &token, VOTER2), Uint128::new(200));
+            assert_eq!(
+                cw20_balance(&app, &token, vote_addr.as_str()),
+                Uint128::zero()
+            );
+
+            let err = app
+                .execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &claim, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::AlreadyClaimed {}, err.downcast().unwrap());
+        }
+
+        #[test]
+        fn deposit_rejects_other_tokens() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let token = instantiate_cw20(&mut app, &[(OWNER, 1_000)]);
+            let other = instantiate_cw20(&mut app, &[(OWNER, 1_000)]);
+            create_cw20_box(&mut app, &vote_addr, &token);
+
+            let err = send_deposit(&mut app, &other, &vote_addr, OWNER, 100).unwrap_err();
+            assert_eq!(ContractError::NotSupportToken {}, err);
+
+            create_box(&mut app, &vote_addr, "native poll", Some(NATIVE_DENOM));
+            let msg = Cw20ExecuteMsg::Send {
+                contract: vote_addr.to_string(),
+                amount: Uint128::new(100),
+                msg: to_binary(&ReceiveMsg::deposit { id: Uint64::new(2) }).unwrap(),
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(OWNER), token.clone(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::NotSupportToken {}, err.downcast().unwrap());
+        }
+
+        #[test]
+        fn votebox_takes_a_single_reward_token() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let token = instantiate_cw20(&mut app, &[(OWNER, 1_000)]);
+            let config = BoxConfig {
+                native_denom: Some(NATIVE_DENOM.to_string()),
+                cw20_addr: Some(token.to_string()),
+                ..BoxConfig::default()
+            };
+            let err = try_create_box(&mut app, &vote_addr, "mixed poll", config).unwrap_err();
+            assert_eq!(ContractError::AmbiguousRewardToken {}, err);
+        }
+    }
+
+    mod ballot_changes {
+        use super::*;
+
+        fn try_change(
+            app: &mut App,
+            vote_addr: &Addr,
+            voter: &str,
+            vote_type: i32,
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::change_vote {
+                id: Uint64::new(1),
+                vote_type,
+            };
+            app.execute_contract(Addr::unchecked(voter), vote_addr.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        fn try_retract(
+            app: &mut App,
+            vote_addr: &Addr,
+            voter: &str,
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::retract_vote { id: Uint64::new(1) };
+            app.execute_contract(Addr::unchecked(voter), vote_addr.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        #[test]
+        fn change_moves_the_vote() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 2);
+
+            let res = try_change(&mut app, &vote_addr, VOTER1, 0).unwrap();
+            let wasm = res.events.iter().find(|ev| ev.ty == "wasm").unwrap();
+            let attr = |key: &str| {
+                wasm.attributes
+                    .iter()
+                    .find(|attr| attr.key == key)
+                    .map(|attr| attr.value.clone())
+            };
+            assert_eq!(attr("old_vote_type"), Some("2".to_string()));
+            assert_eq!(attr("new_vote_type"), Some("0".to_string()));
+
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "yes"), Uint128::new(1));
+            assert_eq!(count(&res, "no"), Uint128::new(1));
+            assert_eq!(res.voter_count, Uint128::new(2));
+
+            let err = try_change(&mut app, &vote_addr, VOTER1, 7).unwrap_err();
+            assert_eq!(ContractError::InvalidVote {}, err);
+            let err = try_change(&mut app, &vote_addr, STRANGER, 0).unwrap_err();
+            assert_eq!(ContractError::NotVoted {}, err);
+
+            end_voting(&mut app);
+            let err = try_change(&mut app, &vote_addr, VOTER2, 0).unwrap_err();
+            assert_eq!(ContractError::Expired {}, err);
+        }
+
+        #[test]
+        fn retract_frees_the_voter() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            vote(&mut app, &vote_addr, VOTER1, 3);
+
+            try_retract(&mut app, &vote_addr, VOTER1).unwrap();
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "no_with_veto"), Uint128::zero());
+            assert_eq!(res.voter_count, Uint128::zero());
+
+            let err = try_retract(&mut app, &vote_addr, VOTER1).unwrap_err();
+            assert_eq!(ContractError::NotVoted {}, err);
+
+            // a retracted voter may vote again
+            vote(&mut app, &vote_addr, VOTER1, 1);
+            assert_eq!(
+                count(&query_box(&app, &vote_addr, 1), "abstain"),
+                Uint128::new(1)
+            );
+        }
+
+        #[test]
+        fn reset_clears_ballots() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            vote(&mut app, &vote_addr, VOTER1, 2);
+
+            let reset = ExecuteMsg::vote_reset { id: Uint64::new(1) };
+            app.execute_contract(Addr::unchecked(OWNER), vote_addr.clone(), &reset, &[])
+                .unwrap();
+
+            let err = try_change(&mut app, &vote_addr, VOTER1, 0).unwrap_err();
+            assert_eq!(ContractError::NotVoted {}, err);
+        }
+    }
+
+    mod ballots {
+        use super::*;
+        use crate::msg::{BallotListResponse, BallotResponse};
+
+        fn list_ballots(
+            app: &App,
+            vote_addr: &Addr,
+            start_after: Option<&str>,
+            limit: Option<u32>,
+        ) -> Vec<BallotResponse> {
+            let msg = QueryMsg::list_ballots {
+                id: Uint64::new(1),
+                start_after: start_after.map(String::from),
+                limit,
+            };
+            let res: BallotListResponse = app.wrap().query_wasm_smart(vote_addr, &msg).unwrap();
+            res.ballots
+        }
+
+        #[test]
+        fn ballots_record_each_vote() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+
+            let msg = ExecuteMsg::vote {
+                id: Uint64::new(1),
+                vote_type: 2,
+                proof: None,
+                memo: Some("ship it".to_string()),
+                votes: None,
+            };
+            app.execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &msg, &[])
+                .unwrap();
+            let block = app.block_info();
+
+            let msg = QueryMsg::query_ballot {
+                id: Uint64::new(1),
+                voter: VOTER1.to_string(),
+            };
+            let ballot: BallotResponse = app.wrap().query_wasm_smart(&vote_addr, &msg).unwrap();
+            assert_eq!(
+                ballot,
+                BallotResponse {
+                    voter: VOTER1.to_string(),
+                    vote_type: 2,
+                    ranking: vec![],
+                    approvals: vec![],
+                    allocation: vec![],
+                    weight: Uint128::new(1),
+                    height: block.height,
+                    time: block.time,
+                    memo: Some("ship it".to_string()),
+                }
+            );
+
+            let msg = QueryMsg::query_ballot {
+                id: Uint64::new(1),
+                voter: VOTER2.to_string(),
+            };
+            app.wrap()
+                .query_wasm_smart::<BallotResponse>(&vote_addr, &msg)
+                .unwrap_err();
+        }
+
+        #[test]
+        fn ballots_are_paginated() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            vote(&mut app, &vote_addr, VOTER2, 0);
+            vote(&mut app, &vote_addr, STRANGER, 1);
+            vote(&mut app, &vote_addr, VOTER1, 2);
+
+            let voters: Vec<_> = list_ballots(&app, &vote_addr, None, Some(2))
+                .into_iter()
+                .map(|ballot| (ballot.voter, ballot.vote_type))
+                .collect();
+            assert_eq!(
+                voters,
+                vec![(STRANGER.to_string(), 1), (VOTER1.to_string(), 2)]
+            );
+
+            let rest = list_ballots(&app, &vote_addr, Some(VOTER1), None);
+            assert_eq!(rest.len(), 1);
+            assert_eq!(rest[0].voter, VOTER2);
+        }
+
+        #[test]
+        fn memo_length_is_limited() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+
+            let msg = ExecuteMsg::vote {
+                id: Uint64::new(1),
+                vote_type: 2,
+                proof: None,
+                memo: Some("x".repeat(257)),
+                votes: None,
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(VOTER1), vote_addr, &msg, &[])
+                .unwrap_err();
+            assert_eq!(
+                ContractError::MemoTooLong { max: 256 },
+                err.downcast().unwrap()
+            );
+        }
+    }
+
+    mod token_weighted {
+        use super::*;
+
+        fn create_weighted_box(app: &mut App, vote_addr: &Addr, token: &Addr, lock: bool) {
+            let config = BoxConfig {
+                voting_power: Some(VotingPower::Cw20Balance {
+                    token: token.clone(),
+                    lock,
+                }),
+                ..BoxConfig::default()
+            };
+            try_create_box(app, vote_addr, "weighted poll", config).unwrap();
+        }
+
+        #[test]
+        fn tallies_use_token_balances() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let token = instantiate_cw20(&mut app, &[(VOTER1, 700), (VOTER2, 300)]);
+            create_weighted_box(&mut app, &vote_addr, &token, false);
+
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 0);
+            let err = try_vote(&mut app, &vote_addr, STRANGER, 0).unwrap_err();
+            assert_eq!(ContractError::NoVotingPower {}, err);
+
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "yes"), Uint128::new(700));
+            assert_eq!(count(&res, "no"), Uint128::new(300));
+            assert_eq!(res.voter_count, Uint128::new(2));
+            // balances are untouched without a lock
+            assert_eq!(cw20_balance(&app, &token, VOTER1), Uint128::new(700));
+        }
+
+        #[test]
+        fn locked_balances_return_after_deadline() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let token = instantiate_cw20(&mut app, &[(VOTER1, 700)]);
+            create_weighted_box(&mut app, &vote_addr, &token, true);
+
+            // without an allowance the lock transfer fails and the vote is rolled back
+            let msg = ExecuteMsg::vote {
+                id: Uint64::new(1),
+                vote_type: 2,
+                proof: None,
+                memo: None,
+                votes: None,
+            };
+            app.execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(query_box(&app, &vote_addr, 1).voter_count, Uint128::zero());
+
+            let allow = Cw20ExecuteMsg::IncreaseAllowance {
+                spender: vote_addr.to_string(),
+                amount: Uint128::new(700),
+                expires: None,
+            };
+            app.execute_contract(Addr::unchecked(VOTER1), token.clone(), &allow, &[])
+                .unwrap();
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            assert_eq!(cw20_balance(&app, &token, VOTER1), Uint128::zero());
+            assert_eq!(
+                count(&query_box(&app, &vote_addr, 1), "yes"),
+                Uint128::new(700)
+            );
+
+            let unlock = ExecuteMsg::unlock { id: Uint64::new(1) };
+            let err = app
+                .execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &unlock, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::Unexpired {}, err.downcast().unwrap());
+
+            end_voting(&mut app);
+            app.execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &unlock, &[])
+                .unwrap();
+            assert_eq!(cw20_balance(&app, &token, VOTER1), Uint128::new(700));
+
+            let err = app
+                .execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &unlock, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::NothingLocked {}, err.downcast().unwrap());
+        }
+    }
+
+    mod group_gated {
+        use super::*;
+        use cw4::Member;
+
+        pub fn contract_cw4_group() -> Box<dyn Contract<Empty>> {
+            let contract = ContractWrapper::new(
+                cw4_group::contract::execute,
+                cw4_group::contract::instantiate,
+                cw4_group::contract::query,
+            );
+            Box::new(contract)
+        }
+
+        fn instantiate_group(app: &mut App, members: &[(&str, u64)]) -> Addr {
+            let group_id = app.store_code(contract_cw4_group());
+            let msg = cw4_group::msg::InstantiateMsg {
+                admin: Some(ADMIN.to_string()),
+                members: members
+                    .iter()
+                    .map(|(addr, weight)| Member {
+                        addr: addr.to_string(),
+                        weight: *weight,
+                    })
+                    .collect(),
+            };
+            app.instantiate_contract(group_id, Addr::unchecked(ADMIN), &msg, &[], "group", None)
+                .unwrap()
+        }
+
+        #[test]
+        fn only_members_vote_with_their_weight() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let group = instantiate_group(&mut app, &[(VOTER1, 5), (VOTER2, 2), (OWNER, 0)]);
+            let config = BoxConfig {
+                voting_power: Some(VotingPower::Cw4Group {
+                    group: group.clone(),
+                }),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "committee poll", config).unwrap();
+
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 3);
+
+            let err = try_vote(&mut app, &vote_addr, STRANGER, 2).unwrap_err();
+            assert_eq!(ContractError::NotMember {}, err);
+            let err = try_vote(&mut app, &vote_addr, OWNER, 2).unwrap_err();
+            assert_eq!(ContractError::NoVotingPower {}, err);
+
+            // membership is checked when the vote is cast
+            let update = cw4_group::msg::ExecuteMsg::UpdateMembers {
+                remove: vec![],
+                add: vec![Member {
+                    addr: STRANGER.to_string(),
+                    weight: 3,
+                }],
+            };
+            app.execute_contract(Addr::unchecked(ADMIN), group, &update, &[])
+                .unwrap();
+            vote(&mut app, &vote_addr, STRANGER, 2);
+
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "yes"), Uint128::new(8));
+            assert_eq!(count(&res, "no_with_veto"), Uint128::new(2));
+            assert_eq!(res.voter_count, Uint128::new(3));
+        }
+
+        #[test]
+        fn outcome_uses_the_group_total_weight() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let group = instantiate_group(&mut app, &[(VOTER1, 3), (VOTER2, 2), (STRANGER, 5)]);
+            let config = BoxConfig {
+                voting_power: Some(VotingPower::Cw4Group { group }),
+                thresholds: Some(gov_thresholds()),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "committee poll", config).unwrap();
+
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            end_voting(&mut app);
+            // 3 of 10 voted
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(res.outcome, Outcome::QuorumNotMet {});
+        }
+    }
+
+    mod allowlist {
+        use super::*;
+        use crate::msg::EligibleVotersResponse;
+
+        fn eligible_voters(
+            app: &App,
+            vote_addr: &Addr,
+            start_after: Option<&str>,
+            limit: Option<u32>,
+        ) -> Vec<String> {
+            let msg = QueryMsg::list_eligible_voters {
+                id: Uint64::new(1),
+                start_after: start_after.map(String::from),
+                limit,
+            };
+            let res: EligibleVotersResponse = app.wrap().query_wasm_smart(vote_addr, &msg).unwrap();
+            res.voters
+        }
+
+        #[test]
+        fn only_listed_addresses_vote() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                allowlist: Some(vec![VOTER1.to_string(), VOTER2.to_string()]),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "committee poll", config).unwrap();
+
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            let err = try_vote(&mut app, &vote_addr, STRANGER, 2).unwrap_err();
+            assert_eq!(ContractError::NotEligible {}, err);
+
+            let update = ExecuteMsg::update_eligible_voters {
+                id: Uint64::new(1),
+                add: vec![STRANGER.to_string()],
+                remove: vec![VOTER2.to_string()],
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &update, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+            app.execute_contract(Addr::unchecked(OWNER), vote_addr.clone(), &update, &[])
+                .unwrap();
+
+            vote(&mut app, &vote_addr, STRANGER, 0);
+            let err = try_vote(&mut app, &vote_addr, VOTER2, 2).unwrap_err();
+            assert_eq!(ContractError::NotEligible {}, err);
+
+            let res = query_box(&app, &vote_addr, 1);
+            assert!(res.allowlist);
+            assert_eq!(res.voter_count, Uint128::new(2));
+        }
+
+        #[test]
+        fn eligible_voters_are_paginated() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                allowlist: Some(vec![
+                    VOTER2.to_string(),
+                    VOTER1.to_string(),
+                    STRANGER.to_string(),
+                ]),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "committee poll", config).unwrap();
+
+            let all = eligible_voters(&app, &vote_addr, None, None);
+            assert_eq!(all, vec![STRANGER, VOTER1, VOTER2]);
+
+            let first = eligible_voters(&app, &vote_addr, None, Some(2));
+            assert_eq!(first, vec![STRANGER, VOTER1]);
+            let rest = eligible_voters(&app, &vote_addr, Some(VOTER1), Some(2));
+            assert_eq!(rest, vec![VOTER2]);
+        }
+
+        #[test]
+        fn open_voteboxes_have_no_allowlist() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "open poll", None);
+            vote(&mut app, &vote_addr, STRANGER, 2);
+
+            let update = ExecuteMsg::update_eligible_voters {
+                id: Uint64::new(1),
+                add: vec![VOTER1.to_string()],
+                remove: vec![],
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(OWNER), vote_addr.clone(), &update, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::NoAllowlist {}, err.downcast().unwrap());
+        }
+    }
+
+    mod merkle {
+        use super::*;
+        use crate::msg::MerkleProof;
+        use sha2::{Digest, Sha256};
+
+        fn leaf(address: &str, weight: u128) -> [u8; 32] {
+            Sha256::digest(format!("{}{}", address, weight).as_bytes()).into()
+        }
+
+        fn parent(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+            let mut pair = [a, b];
+            pair.sort_unstable();
+            Sha256::digest(&pair.concat()).into()
+        }
+
+        fn try_vote_with_proof(
+            app: &mut App,
+            vote_addr: &Addr,
+            voter: &str,
+            weight: u128,
+            proof: &[[u8; 32]],
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::vote {
+                id: Uint64::new(1),
+                vote_type: 2,
+                proof: Some(MerkleProof {
+                    weight: Uint128::new(weight),
+                    proof: proof.iter().map(hex::encode).collect(),
+                }),
+                memo: None,
+                votes: None,
+            };
+            app.execute_contract(Addr::unchecked(voter), vote_addr.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        #[test]
+        fn proofs_decide_eligibility_and_weight() {
+            let (mut app, vote_addr) = proper_instantiate();
+            //        root
+            //       /    \
+            //     ab      c
+            //    /  \
+            //   a    b
+            let a = leaf(VOTER1, 10);
+            let b = leaf(VOTER2, 25);
+            let c = leaf(OWNER, 5);
+            let ab = parent(a, b);
+            let root = parent(ab, c);
+
+            let config = BoxConfig {
+                voting_power: Some(VotingPower::Merkle {
+                    root: hex::encode(root),
+                }),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "snapshot poll", config).unwrap();
+
+            let err = try_vote(&mut app, &vote_addr, VOTER1, 2).unwrap_err();
+            assert_eq!(ContractError::MissingProof {}, err);
+            let err = try_vote_with_proof(&mut app, &vote_addr, VOTER1, 11, &[b, c]).unwrap_err();
+            assert_eq!(ContractError::InvalidProof {}, err);
+            let err = try_vote_with_proof(&mut app, &vote_addr, STRANGER, 10, &[b, c]).unwrap_err();
+            assert_eq!(ContractError::InvalidProof {}, err);
+
+            try_vote_with_proof(&mut app, &vote_addr, VOTER1, 10, &[b, c]).unwrap();
+            try_vote_with_proof(&mut app, &vote_addr, VOTER2, 25, &[a, c]).unwrap();
+            try_vote_with_proof(&mut app, &vote_addr, OWNER, 5, &[ab]).unwrap();
+
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "yes"), Uint128::new(40));
+            assert_eq!(res.voter_count, Uint128::new(3));
+        }
+
+        #[test]
+        fn root_must_be_a_sha256_hash() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                voting_power: Some(VotingPower::Merkle {
+                    root: "not a root".to_string(),
+                }),
+                ..BoxConfig::default()
+            };
+            let err = try_create_box(&mut app, &vote_addr, "snapshot poll", config).unwrap_err();
+            assert_eq!(ContractError::InvalidMerkleRoot {}, err);
+        }
+    }
+
+    mod stake_weighted {
+        use super::*;
+        use crate::contract::{execute, instantiate, query};
+        use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+        use cosmwasm_std::{coin, from_binary, FullDelegation};
+
+        fn delegation(delegator: &str, validator: &str, amount: u128) -> FullDelegation {
+            FullDelegation {
+                delegator: Addr::unchecked(delegator),
+                validator: validator.to_string(),
+                amount: coin(amount, "ustake"),
+                can_redelegate: coin(0, "ustake"),
+                accumulated_rewards: vec![],
+            }
+        }
+
+        #[test]
+        fn tallies_use_delegated_stake() {
+            let mut deps = mock_dependencies();
+            deps.querier.update_staking(
+                "ustake",
+                &[],
+                &[
+                    delegation(VOTER1, "validator1", 100),
+                    delegation(VOTER1, "validator2", 50),
+                    delegation(VOTER2, "validator1", 40),
+                ],
+            );
+            let msg = InstantiateMsg { admin: None };
+            instantiate(deps.as_mut(), mock_env(), mock_info(ADMIN, &[]), msg).unwrap();
+
+            let config = BoxConfig {
+                voting_power: Some(VotingPower::Staked {}),
+                ..BoxConfig::default()
+            };
+            let msg = create_box_msg("signalling poll", config);
+            execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), msg).unwrap();
+
+            for (voter, vote_type) in [(VOTER1, 2), (VOTER2, 0)] {
+                execute(
+                    deps.as_mut(),
+                    mock_env(),
+                    mock_info(voter, &[]),
+                    vote_msg(vote_type),
+                )
+                .unwrap();
+            }
+            let err = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(STRANGER, &[]),
+                vote_msg(2),
+            )
+            .unwrap_err();
+            assert_eq!(ContractError::NoVotingPower {}, err);
+
+            let msg = QueryMsg::query_vote { id: Uint64::new(1) };
+            let res: VoteResponse =
+                from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            assert_eq!(count(&res, "yes"), Uint128::new(150));
+            assert_eq!(count(&res, "no"), Uint128::new(40));
+            assert_eq!(res.voter_count, Uint128::new(2));
+        }
+    }
+
+    mod custom_options {
+        use super::*;
+
+        fn labels(labels: &[&str]) -> Option<Vec<String>> {
+            Some(labels.iter().map(|label| label.to_string()).collect())
+        }
+
+        #[test]
+        fn votes_are_tallied_per_option() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                options: labels(&["march", "april", "may"]),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "release date", config).unwrap();
+
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            vote(&mut app, &vote_addr, OWNER, 0);
+
+            let res = query_box(&app, &vote_addr, 1);
+            let options: Vec<_> = res.options.iter().map(|o| o.label.as_str()).collect();
+            assert_eq!(options, ["march", "april", "may"]);
+            assert_eq!(count(&res, "march"), Uint128::new(1));
+            assert_eq!(count(&res, "april"), Uint128::zero());
+            assert_eq!(count(&res, "may"), Uint128::new(2));
+
+            let err = try_vote(&mut app, &vote_addr, STRANGER, 3).unwrap_err();
+            assert_eq!(ContractError::InvalidVote {}, err);
+            let err = try_vote(&mut app, &vote_addr, STRANGER, -1).unwrap_err();
+            assert_eq!(ContractError::InvalidVote {}, err);
+        }
+
+        #[test]
+        fn classic_options_by_default() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+
+            let res = query_box(&app, &vote_addr, 1);
+            let options: Vec<_> = res.options.iter().map(|o| o.label.as_str()).collect();
+            assert_eq!(options, ["no", "abstain", "yes", "no_with_veto"]);
+        }
+
+        #[test]
+        fn invalid_option_lists_are_rejected() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let invalid = ContractError::InvalidOptions { min: 2, max: 20 };
+
+            let too_many: Vec<String> = (0..21).map(|i| format!("option {}", i)).collect();
+            for options in [
+                labels(&["only"]),
+                Some(too_many),
+                labels(&["same", "same"]),
+                labels(&["ok", " "]),
+            ] {
+                let config = BoxConfig {
+                    options,
+                    ..BoxConfig::default()
+                };
+                let err = try_create_box(&mut app, &vote_addr, "poll", config).unwrap_err();
+                assert_eq!(invalid, err);
+            }
+        }
+    }
+
+    mod ranked_choice {
+        use super::*;
+        use crate::msg::BallotResponse;
+
+        fn create_ranked_box(app: &mut App, vote_addr: &Addr) {
+            let config = BoxConfig {
+                options: Some(vec!["red".into(), "green".into(), "blue".into()]),
+                kind: Some(VoteKind::RankedChoice {}),
+                ..BoxConfig::default()
+            };
+            try_create_box(app, vote_addr, "colour", config).unwrap();
+        }
+
+        fn try_vote_ranked(
+            app: &mut App,
+            vote_addr: &Addr,
+            voter: &str,
+            ranking: &[u32],
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::vote_ranked {
+                id: Uint64::new(1),
+                ranking: ranking.to_vec(),
+                proof: None,
+                memo: None,
+            };
+            app.execute_contract(Addr::unchecked(voter), vote_addr.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        #[test]
+        fn runoff_rounds_are_reported_after_the_deadline() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_ranked_box(&mut app, &vote_addr);
+
+            try_vote_ranked(&mut app, &vote_addr, VOTER1, &[0]).unwrap();
+            try_vote_ranked(&mut app, &vote_addr, VOTER2, &[1, 0]).unwrap();
+            try_vote_ranked(&mut app, &vote_addr, OWNER, &[2, 1]).unwrap();
+            try_vote_ranked(&mut app, &vote_addr, STRANGER, &[1, 2]).unwrap();
+
+            // tallies count first preferences
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "green"), Uint128::new(2));
+
+            let ballot: BallotResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    &vote_addr,
+                    &QueryMsg::query_ballot {
+                        id: Uint64::new(1),
+                        voter: OWNER.to_string(),
+                    },
+                )
+                .unwrap();
+            assert_eq!(ballot.vote_type, 2);
+            assert_eq!(ballot.ranking, vec![2, 1]);
+
+            assert!(query_result(&app, &vote_addr).is_err());
+            end_voting(&mut app);
+
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(res.rounds.len(), 2);
+            // green and red together could beat blue, so only the later index goes
+            assert_eq!(res.rounds[0].eliminated, vec![2]);
+            assert_eq!(res.rounds[1].tallies[1], Uint128::new(3));
+            assert_eq!(res.rounds[1].exhausted, Uint128::zero());
+            assert_eq!(res.outcome, Outcome::Winner { option: 1 });
+        }
+
+        #[test]
+        fn ballots_must_match_the_box_kind() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_ranked_box(&mut app, &vote_addr);
+
+            let err = try_vote(&mut app, &vote_addr, VOTER1, 0).unwrap_err();
+            assert_eq!(ContractError::WrongVoteKind {}, err);
+            for ranking in [&[][..], &[3], &[0, 1, 0]] {
+                let err = try_vote_ranked(&mut app, &vote_addr, VOTER1, ranking).unwrap_err();
+                assert_eq!(ContractError::InvalidRanking {}, err);
+            }
+
+            try_vote_ranked(&mut app, &vote_addr, VOTER1, &[2, 0, 1]).unwrap();
+            let err = try_vote_ranked(&mut app, &vote_addr, VOTER1, &[1]).unwrap_err();
+            assert_eq!(ContractError::VoterRepeat {}, err);
+
+            let msg = ExecuteMsg::change_vote {
+                id: Uint64::new(1),
+                vote_type: 1,
+            };
+            let err: ContractError = app
+                .execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &msg, &[])
+                .unwrap_err()
+                .downcast()
+                .unwrap();
+            assert_eq!(ContractError::WrongVoteKind {}, err);
+
+            // plain boxes only take single-option ballots
+            create_box(&mut app, &vote_addr, "plain", None);
+            let msg = ExecuteMsg::vote_ranked {
+                id: Uint64::new(2),
+                ranking: vec![0],
+                proof: None,
+                memo: None,
+            };
+            let err: ContractError = app
+                .execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &msg, &[])
+                .unwrap_err()
+                .downcast()
+                .unwrap();
+            assert_eq!(ContractError::WrongVoteKind {}, err);
+        }
+    }
+
+    mod approval {
+        use super::*;
+
+        fn try_vote_approval(
+            app: &mut App,
+            vote_addr: &Addr,
+            voter: &str,
+            options: &[u32],
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::vote_approval {
+                id: Uint64::new(1),
+                options: options.to_vec(),
+                proof: None,
+                memo: None,
+            };
+            app.execute_contract(Addr::unchecked(voter), vote_addr.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        #[test]
+        fn every_approved_option_gets_the_full_weight() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                options: Some(vec!["pizza".into(), "sushi".into(), "tacos".into()]),
+                kind: Some(VoteKind::Approval {}),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "lunch", config).unwrap();
+
+            try_vote_approval(&mut app, &vote_addr, VOTER1, &[0, 2]).unwrap();
+            try_vote_approval(&mut app, &vote_addr, VOTER2, &[2]).unwrap();
+            try_vote_approval(&mut app, &vote_addr, OWNER, &[1, 0, 2]).unwrap();
+
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "pizza"), Uint128::new(2));
+            assert_eq!(count(&res, "sushi"), Uint128::new(1));
+            assert_eq!(count(&res, "tacos"), Uint128::new(3));
+            assert_eq!(res.voter_count, Uint128::new(3));
+
+            let err = try_vote_approval(&mut app, &vote_addr, VOTER1, &[1]).unwrap_err();
+            assert_eq!(ContractError::VoterRepeat {}, err);
+
+            // retracting takes the weight back from every approved option
+            let msg = ExecuteMsg::retract_vote { id: Uint64::new(1) };
+            app.execute_contract(Addr::unchecked(OWNER), vote_addr.clone(), &msg, &[])
+                .unwrap();
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "pizza"), Uint128::new(1));
+            assert_eq!(count(&res, "sushi"), Uint128::zero());
+            assert_eq!(count(&res, "tacos"), Uint128::new(2));
+
+            end_voting(&mut app);
+            let res: ResultResponse = app
+                .wrap()
+                .query_wasm_smart(&vote_addr, &QueryMsg::query_result { id: Uint64::new(1) })
+                .unwrap();
+            assert_eq!(res.outcome, Outcome::Winner { option: 2 });
+        }
+
+        #[test]
+        fn invalid_selections_are_rejected() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                kind: Some(VoteKind::Approval {}),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "poll", config).unwrap();
+
+            let err = try_vote_approval(&mut app, &vote_addr, VOTER1, &[]).unwrap_err();
+            assert_eq!(ContractError::EmptyApproval {}, err);
+            let err = try_vote_approval(&mut app, &vote_addr, VOTER1, &[2, 1, 2]).unwrap_err();
+            assert_eq!(ContractError::DuplicateApproval {}, err);
+            let err = try_vote_approval(&mut app, &vote_addr, VOTER1, &[1, 4]).unwrap_err();
+            assert_eq!(ContractError::InvalidVote {}, err);
+            let err = try_vote(&mut app, &vote_addr, VOTER1, 1).unwrap_err();
+            assert_eq!(ContractError::WrongVoteKind {}, err);
+
+            // failed ballots leave no trace
+            assert_eq!(query_box(&app, &vote_addr, 1).voter_count, Uint128::zero());
+        }
+    }
+
+    mod quadratic {
+        use super::*;
+        use crate::msg::CreditsResponse;
+
+        fn try_buy(
+            app: &mut App,
+            vote_addr: &Addr,
+            voter: &str,
+            vote_type: i32,
+            votes: u128,
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::vote {
+                id: Uint64::new(1),
+                vote_type,
+                proof: None,
+                memo: None,
+                votes: Some(Uint128::new(votes)),
+            };
+            app.execute_contract(Addr::unchecked(voter), vote_addr.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        fn credits(app: &App, vote_addr: &Addr, voter: &str) -> CreditsResponse {
+            let msg = QueryMsg::query_credits {
+                id: Uint64::new(1),
+                voter: voter.to_string(),
+            };
+            app.wrap().query_wasm_smart(vote_addr, &msg).unwrap()
+        }
+
+        #[test]
+        fn budget_is_enforced_across_ballots() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                kind: Some(VoteKind::Quadratic {
+                    credits: Uint128::new(20),
+                }),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "budget", config).unwrap();
+            assert_eq!(
+                credits(&app, &vote_addr, VOTER1).remaining,
+                Uint128::new(20)
+            );
+
+            // 3 votes on yes cost 9 credits
+            try_buy(&mut app, &vote_addr, VOTER1, 2, 3).unwrap();
+            // a 4th vote on yes costs 16 - 9 = 7 more
+            try_buy(&mut app, &vote_addr, VOTER1, 2, 1).unwrap();
+            let res = credits(&app, &vote_addr, VOTER1);
+            assert_eq!(res.spent, Uint128::new(16));
+            assert_eq!(res.remaining, Uint128::new(4));
+
+            let err = try_buy(&mut app, &vote_addr, VOTER1, 2, 1).unwrap_err();
+            assert_eq!(
+                ContractError::InsufficientCredits {
+                    remaining: Uint128::new(4)
+                },
+                err
+            );
+            // spreading is cheaper, 2 votes on abstain cost 4
+            try_buy(&mut app, &vote_addr, VOTER1, 1, 2).unwrap();
+            assert_eq!(credits(&app, &vote_addr, VOTER1).remaining, Uint128::zero());
+            // the default is a single vote
+            vote(&mut app, &vote_addr, VOTER2, 0);
+
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "yes"), Uint128::new(4));
+            assert_eq!(count(&res, "abstain"), Uint128::new(2));
+            assert_eq!(count(&res, "no"), Uint128::new(1));
+            assert_eq!(res.voter_count, Uint128::new(2));
+
+            // retracting returns every vote bought
+            let msg = ExecuteMsg::retract_vote { id: Uint64::new(1) };
+            app.execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &msg, &[])
+                .unwrap();
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "yes"), Uint128::zero());
+            assert_eq!(count(&res, "abstain"), Uint128::zero());
+            assert_eq!(
+                credits(&app, &vote_addr, VOTER1).remaining,
+                Uint128::new(20)
+            );
+
+            let err = try_buy(&mut app, &vote_addr, VOTER2, 0, 0).unwrap_err();
+            assert_eq!(ContractError::InvalidVote {}, err);
+        }
+
+        #[test]
+        fn budget_must_not_be_zero() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                kind: Some(VoteKind::Quadratic {
+                    credits: Uint128::zero(),
+                }),
+                ..BoxConfig::default()
+            };
+            let err = try_create_box(&mut app, &vote_addr, "budget", config).unwrap_err();
+            assert_eq!(ContractError::InvalidCredits {}, err);
+        }
+    }
+
+    mod secret_ballot {
+        use super::*;
+        use sha2::{Digest, Sha256};
+
+        const REVEAL_DEADLINE: u64 = DEADLINE + 50;
+
+        fn commitment(voter: &str, vote_type: i32, salt: &str) -> String {
+            let preimage = format!("1:{}:{}:{}", voter, vote_type, salt);
+            hex::encode(Sha256::digest(preimage.as_bytes()))
+        }
+
+        fn try_commit(
+            app: &mut App,
+            vote_addr: &Addr,
+            voter: &str,
+            commitment: String,
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::commit_vote {
+                id: Uint64::new(1),
+                commitment,
+                proof: None,
+                memo: None,
+            };
+            app.execute_contract(Addr::unchecked(voter), vote_addr.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        fn try_reveal(
+            app: &mut App,
+            vote_addr: &Addr,
+            voter: &str,
+            vote_type: i32,
+            salt: &str,
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::reveal_vote {
+                id: Uint64::new(1),
+                vote_type,
+                salt: salt.to_string(),
+            };
+            app.execute_contract(Addr::unchecked(voter), vote_addr.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        #[test]
+        fn only_revealed_votes_are_tallied() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                reveal_deadline: Some(Scheduled::AtHeight(REVEAL_DEADLINE)),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "secret", config).unwrap();
+
+            try_commit(
+                &mut app,
+                &vote_addr,
+                VOTER1,
+                commitment(VOTER1, 2, "pepper"),
+            )
+            .unwrap();
+            try_commit(&mut app, &vote_addr, VOTER2, commitment(VOTER2, 0, "salt")).unwrap();
+            try_commit(&mut app, &vote_addr, OWNER, commitment(OWNER, 2, "shy")).unwrap();
+
+            let err =
+                try_commit(&mut app, &vote_addr, VOTER1, commitment(VOTER1, 0, "x")).unwrap_err();
+            assert_eq!(ContractError::VoterRepeat {}, err);
+            let err = try_commit(&mut app, &vote_addr, STRANGER, "beef".into()).unwrap_err();
+            assert_eq!(ContractError::InvalidCommitment {}, err);
+            let err = try_vote(&mut app, &vote_addr, STRANGER, 2).unwrap_err();
+            assert_eq!(ContractError::WrongVoteKind {}, err);
+
+            let err = try_reveal(&mut app, &vote_addr, VOTER1, 2, "pepper").unwrap_err();
+            assert_eq!(ContractError::Unexpired {}, err);
+            assert_eq!(
+                count(&query_box(&app, &vote_addr, 1), "yes"),
+                Uint128::zero()
+            );
+
+            end_voting(&mut app);
+            let err = try_reveal(&mut app, &vote_addr, VOTER1, 0, "pepper").unwrap_err();
+            assert_eq!(ContractError::InvalidReveal {}, err);
+            try_reveal(&mut app, &vote_addr, VOTER1, 2, "pepper").unwrap();
+            try_reveal(&mut app, &vote_addr, VOTER2, 0, "salt").unwrap();
+            let err = try_reveal(&mut app, &vote_addr, VOTER2, 0, "salt").unwrap_err();
+            assert_eq!(ContractError::NotVoted {}, err);
+
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "yes"), Uint128::new(1));
+            assert_eq!(count(&res, "no"), Uint128::new(1));
+            assert_eq!(res.voter_count, Uint128::new(2));
+
+            app.update_block(|block| block.height = REVEAL_DEADLINE);
+            let err = try_reveal(&mut app, &vote_addr, OWNER, 2, "shy").unwrap_err();
+            assert_eq!(ContractError::RevealClosed {}, err);
+
+            let res: ResultResponse = app
+                .wrap()
+                .query_wasm_smart(&vote_addr, &QueryMsg::query_result { id: Uint64::new(1) })
+                .unwrap();
+            assert_eq!(
+                res.outcome,
+                Outcome::Tie {
+                    options: vec![0, 2]
+                }
+            );
+            assert_eq!(res.unrevealed, 1);
+            assert_eq!(res.unrevealed_weight, Uint128::new(1));
+        }
+
+        #[test]
+        fn copied_commitments_cannot_be_revealed() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                reveal_deadline: Some(Scheduled::AtHeight(REVEAL_DEADLINE)),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "secret", config).unwrap();
+
+            let copied = commitment(VOTER1, 2, "pepper");
+            try_commit(&mut app, &vote_addr, VOTER1, copied.clone()).unwrap();
+            try_commit(&mut app, &vote_addr, VOTER2, copied).unwrap();
+
+            end_voting(&mut app);
+            try_reveal(&mut app, &vote_addr, VOTER1, 2, "pepper").unwrap();
+            let err = try_reveal(&mut app, &vote_addr, VOTER2, 2, "pepper").unwrap_err();
+            assert_eq!(ContractError::InvalidReveal {}, err);
+            assert_eq!(
+                count(&query_box(&app, &vote_addr, 1), "yes"),
+                Uint128::new(1)
+            );
+        }
+
+        #[test]
+        fn reveal_deadline_is_validated() {
+            let (mut app, vote_addr) = proper_instantiate();
+            for (reveal_deadline, kind, expected) in [
+                (
+                    Scheduled::AtHeight(DEADLINE),
+                    None,
+                    ContractError::InvalidRevealDeadline {},
+                ),
+                (
+                    Scheduled::AtTime(cosmwasm_std::Timestamp::from_seconds(4_000_000_000)),
+                    None,
+                    ContractError::InvalidRevealDeadline {},
+                ),
+                (
+                    Scheduled::AtHeight(REVEAL_DEADLINE),
+                    Some(VoteKind::RankedChoice {}),
+                    ContractError::UnsupportedSecretBallot {},
+                ),
+            ] {
+                let config = BoxConfig {
+                    reveal_deadline: Some(reveal_deadline),
+                    kind,
+                    ..BoxConfig::default()
+                };
+                let err = try_create_box(&mut app, &vote_addr, "secret", config).unwrap_err();
+                assert_eq!(expected, err);
+            }
+        }
+    }
+
+    mod hidden_tally {
+        use super::*;
+        use crate::msg::{BallotListResponse, VoteBoxListResponse, VoteboxStatistics};
+
+        #[test]
+        fn tallies_are_hidden_until_the_deadline() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                hide_tally_until_end: Some(true),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "brigade bait", config).unwrap();
+            let res = vote(&mut app, &vote_addr, VOTER1, 2);
+            let wasm = res.events.iter().find(|ev| ev.ty == "wasm").unwrap();
+            assert!(!wasm.attributes.iter().any(|attr| attr.key == "count"));
+            vote(&mut app, &vote_addr, VOTER2, 2);
+
+            let res = query_box(&app, &vote_addr, 1);
+            assert!(res.hide_tally_until_end);
+            assert_eq!(count(&res, "yes"), Uint128::zero());
+            assert_eq!(res.voter_count, Uint128::new(2));
+
+            let list: VoteBoxListResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    &vote_addr,
+                    &QueryMsg::get_list {
+                        start_after: None,
+                        limit: None,
+                    },
+                )
+                .unwrap();
+            assert_eq!(count(&list.voteList[0], "yes"), Uint128::zero());
+
+            let stats: VoteboxStatistics = app
+                .wrap()
+                .query_wasm_smart(&vote_addr, &QueryMsg::get_statistics {})
+                .unwrap();
+            assert_eq!(stats.total_yes_count, Uint128::zero());
+
+            let ballots = QueryMsg::list_ballots {
+                id: Uint64::new(1),
+                start_after: None,
+                limit: None,
+            };
+            let hidden: cosmwasm_std::StdResult<BallotListResponse> =
+                app.wrap().query_wasm_smart(&vote_addr, &ballots);
+            assert!(hidden.is_err());
+
+            end_voting(&mut app);
+            assert_eq!(
+                count(&query_box(&app, &vote_addr, 1), "yes"),
+                Uint128::new(2)
+            );
+            let stats: VoteboxStatistics = app
+                .wrap()
+                .query_wasm_smart(&vote_addr, &QueryMsg::get_statistics {})
+                .unwrap();
+            assert_eq!(stats.total_yes_count, Uint128::new(2));
+            let res: BallotListResponse =
+                app.wrap().query_wasm_smart(&vote_addr, &ballots).unwrap();
+            assert_eq!(res.ballots.len(), 2);
+        }
+    }
+
+    mod thresholds {
+        use super::*;
+
+        fn create_allowlisted_box(app: &mut App, vote_addr: &Addr) {
+            let config = BoxConfig {
+                allowlist: Some(vec![
+                    VOTER1.to_string(),
+                    VOTER2.to_string(),
+                    OWNER.to_string(),
+                    STRANGER.to_string(),
+                ]),
+                thresholds: Some(gov_thresholds()),
+                ..BoxConfig::default()
+            };
+            try_create_box(app, vote_addr, "gov", config).unwrap();
+        }
+
+        #[test]
+        fn outcome_follows_the_passing_rules() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_allowlisted_box(&mut app, &vote_addr);
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            vote(&mut app, &vote_addr, OWNER, 0);
+
+            end_voting(&mut app);
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(res.outcome, Outcome::Passed {});
+        }
+
+        #[test]
+        fn veto_rejects_the_votebox() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_allowlisted_box(&mut app, &vote_addr);
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            vote(&mut app, &vote_addr, OWNER, 3);
+            vote(&mut app, &vote_addr, STRANGER, 3);
+
+            end_voting(&mut app);
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(res.outcome, Outcome::RejectedWithVeto {});
+        }
+
+        #[test]
+        fn thresholds_are_validated() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let cases = [
+                (
+                    BoxConfig {
+                        thresholds: Some(Thresholds {
+                            quorum: Decimal::percent(101),
+                            ..gov_thresholds()
+                        }),
+                        total_weight: Some(Uint128::new(10)),
+                        ..BoxConfig::default()
+                    },
+                    ContractError::InvalidThresholds {},
+                ),
+                (
+                    BoxConfig {
+                        thresholds: Some(gov_thresholds()),
+                        options: Some(vec!["a".into(), "b".into()]),
+                        total_weight: Some(Uint128::new(10)),
+                        ..BoxConfig::default()
+                    },
+                    ContractError::UnsupportedThresholds {},
+                ),
+                (
+                    BoxConfig {
+                        thresholds: Some(gov_thresholds()),
+                        ..BoxConfig::default()
+                    },
+                    ContractError::MissingTotalWeight {},
+                ),
+            ];
+            for (config, expected) in cases {
+                let err = try_create_box(&mut app, &vote_addr, "gov", config).unwrap_err();
+                assert_eq!(expected, err);
+            }
+
+            // a creator supplied total makes any voting power rule work
+            let config = BoxConfig {
+                thresholds: Some(gov_thresholds()),
+                total_weight: Some(Uint128::new(10)),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "gov", config).unwrap();
+            vote(&mut app, &vote_addr, VOTER1, 1);
+            end_voting(&mut app);
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(res.outcome, Outcome::QuorumNotMet {});
+        }
+    }
+
+    mod statistics {
+        use super::*;
+        use crate::msg::VoteboxStatistics;
+
+        fn vote_on(app: &mut App, vote_addr: &Addr, id: u64, voter: &str, vote_type: i32) {
+            let msg = ExecuteMsg::vote {
+                id: Uint64::new(id),
+                vote_type,
+                proof: None,
+                memo: None,
+                votes: None,
+            };
+            app.execute_contract(Addr::unchecked(voter), vote_addr.clone(), &msg, &[])
+                .unwrap();
+        }
+
+        #[test]
+        fn closed_voteboxes_count_their_winner() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "yes wins", None);
+            vote_on(&mut app, &vote_addr, 1, VOTER1, 2);
+            vote_on(&mut app, &vote_addr, 1, VOTER2, 2);
+            vote_on(&mut app, &vote_addr, 1, OWNER, 0);
+            create_box(&mut app, &vote_addr, "tied", None);
+            vote_on(&mut app, &vote_addr, 2, VOTER1, 1);
+            vote_on(&mut app, &vote_addr, 2, VOTER2, 3);
+            create_box(&mut app, &vote_addr, "empty", None);
+
+            end_voting(&mut app);
+            let stats: VoteboxStatistics = app
+                .wrap()
+                .query_wasm_smart(&vote_addr, &QueryMsg::get_statistics {})
+                .unwrap();
+            assert_eq!(stats.expired, Uint128::new(3));
+            assert_eq!(stats.yes_won, Uint128::new(1));
+            assert_eq!(stats.no_won, Uint128::zero());
+            assert_eq!(stats.abstain_won, Uint128::zero());
+            assert_eq!(stats.no_veto_won, Uint128::zero());
+            assert_eq!(stats.ties, Uint128::new(1));
+
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(res.outcome, Outcome::Winner { option: 2 });
+        }
+
+        #[test]
+        fn passing_rules_decide_the_winner() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                thresholds: Some(gov_thresholds()),
+                total_weight: Some(Uint128::new(10)),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "governance", config).unwrap();
+            vote_on(&mut app, &vote_addr, 1, VOTER1, 2);
+
+            end_voting(&mut app);
+            let stats: VoteboxStatistics = app
+                .wrap()
+                .query_wasm_smart(&vote_addr, &QueryMsg::get_statistics {})
+                .unwrap();
+            assert_eq!(stats.expired, Uint128::new(1));
+            assert_eq!(stats.yes_won, Uint128::zero());
+
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(res.outcome, Outcome::QuorumNotMet {});
+        }
+    }
+
+    mod tie_break {
+        use super::*;
+
+        fn create_tied_box(app: &mut App, vote_addr: &Addr, tie_break: Option<TieBreak>) {
+            let config = BoxConfig {
+                tie_break,
+                ..BoxConfig::default()
+            };
+            try_create_box(app, vote_addr, "tied", config).unwrap();
+            // yes reaches one vote before no does
+            vote(app, vote_addr, VOTER1, 2);
+            vote(app, vote_addr, VOTER2, 0);
+        }
+
+        fn cast_deciding_vote(
+            app: &mut App,
+            vote_addr: &Addr,
+            sender: &str,
+            option: u32,
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::cast_deciding_vote {
+                id: Uint64::new(1),
+                option,
+            };
+            app.execute_contract(Addr::unchecked(sender), vote_addr.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        #[test]
+        fn status_quo_keeps_the_tie() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_tied_box(&mut app, &vote_addr, None);
+
+            end_voting(&mut app);
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(
+                res.outcome,
+                Outcome::Tie {
+                    options: vec![0, 2]
+                }
+            );
+            assert_eq!(res.decided_by, Some(TieBreak::StatusQuo {}));
+            assert_eq!(
+                query_box(&app, &vote_addr, 1).tie_break,
+                TieBreak::StatusQuo {}
+            );
+        }
+
+        #[test]
+        fn earliest_option_to_reach_the_count_wins() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_tied_box(&mut app, &vote_addr, Some(TieBreak::EarliestToReach {}));
+
+            end_voting(&mut app);
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(res.outcome, Outcome::Winner { option: 2 });
+            assert_eq!(res.decided_by, Some(TieBreak::EarliestToReach {}));
+        }
+
+        #[test]
+        fn owner_casts_the_deciding_vote() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_tied_box(&mut app, &vote_addr, Some(TieBreak::OwnerCastingVote {}));
+
+            let err = cast_deciding_vote(&mut app, &vote_addr, OWNER, 0).unwrap_err();
+            assert_eq!(ContractError::Unexpired {}, err);
+
+            end_voting(&mut app);
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(
+                res.outcome,
+                Outcome::Tie {
+                    options: vec![0, 2]
+                }
+            );
+            assert_eq!(res.decided_by, None);
+
+            let err = cast_deciding_vote(&mut app, &vote_addr, VOTER1, 0).unwrap_err();
+            assert_eq!(ContractError::Unauthorized {}, err);
+            let err = cast_deciding_vote(&mut app, &vote_addr, OWNER, 1).unwrap_err();
+            assert_eq!(ContractError::NotTied {}, err);
+
+            cast_deciding_vote(&mut app, &vote_addr, OWNER, 0).unwrap();
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(res.outcome, Outcome::Winner { option: 0 });
+            assert_eq!(res.decided_by, Some(TieBreak::OwnerCastingVote {}));
+            assert_eq!(query_box(&app, &vote_addr, 1).casting_vote, Some(0));
+
+            let err = cast_deciding_vote(&mut app, &vote_addr, OWNER, 2).unwrap_err();
+            assert_eq!(ContractError::AlreadyDecided {}, err);
+        }
+
+        #[test]
+        fn casting_vote_needs_the_policy() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_tied_box(&mut app, &vote_addr, None);
+
+            end_voting(&mut app);
+            let err = cast_deciding_vote(&mut app, &vote_addr, OWNER, 0).unwrap_err();
+            assert_eq!(ContractError::NoCastingVote {}, err);
+        }
+
+        #[test]
+        fn random_draw_is_deterministic() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_tied_box(&mut app, &vote_addr, Some(TieBreak::Random {}));
+
+            end_voting(&mut app);
+            let res = query_result(&app, &vote_addr).unwrap();
+            let winner = match res.outcome {
+                Outcome::Winner { option } => option,
+                outcome => panic!("unexpected outcome {:?}", outcome),
+            };
+            assert!([0, 2].contains(&winner));
+            assert_eq!(res.decided_by, Some(TieBreak::Random {}));
+
+            app.update_block(|block| block.height += 100);
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(res.outcome, Outcome::Winner { option: winner });
+        }
+    }
+
+    mod delegation {
+        use super::*;
+        use crate::msg::DelegatorsResponse;
+
+        fn try_delegate(
+            app: &mut App,
+            vote_addr: &Addr,
+            delegator: &str,
+            id: Option<u64>,
+            delegate_to: &str,
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::delegate {
+                id: id.map(Uint64::new),
+                delegate_to: delegate_to.to_string(),
+            };
+            app.execute_contract(Addr::unchecked(delegator), vote_addr.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        fn delegate(
+            app: &mut App,
+            vote_addr: &Addr,
+            delegator: &str,
+            id: Option<u64>,
+            delegate_to: &str,
+        ) {
+            try_delegate(app, vote_addr, delegator, id, delegate_to).unwrap();
+        }
+
+        fn delegators(app: &App, vote_addr: &Addr, id: Option<u64>, delegate: &str) -> Vec<String> {
+            let msg = QueryMsg::list_delegators {
+                id: id.map(Uint64::new),
+                delegate: delegate.to_string(),
+                start_after: None,
+                limit: None,
+            };
+            let res: DelegatorsResponse = app.wrap().query_wasm_smart(vote_addr, &msg).unwrap();
+            res.delegators
+        }
+
+        #[test]
+        fn delegate_votes_with_delegated_weight() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            delegate(&mut app, &vote_addr, VOTER1, Some(1), VOTER2);
+            delegate(&mut app, &vote_addr, OWNER, None, VOTER2);
+
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "yes"), Uint128::new(3));
+            assert_eq!(res.voter_count, Uint128::new(1));
+        }
+
+        #[test]
+        fn direct_vote_overrides_the_delegation() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            delegate(&mut app, &vote_addr, VOTER1, None, VOTER2);
+
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            vote(&mut app, &vote_addr, VOTER1, 0);
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "yes"), Uint128::new(1));
+            assert_eq!(count(&res, "no"), Uint128::new(1));
+        }
+
+        #[test]
+        fn chains_are_followed_until_someone_votes() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            delegate(&mut app, &vote_addr, OWNER, None, VOTER1);
+            delegate(&mut app, &vote_addr, VOTER1, None, VOTER2);
+
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            assert_eq!(
+                count(&query_box(&app, &vote_addr, 1), "yes"),
+                Uint128::new(3)
+            );
+
+            // voter1 takes back its own weight and the weight delegated to it
+            vote(&mut app, &vote_addr, VOTER1, 0);
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "yes"), Uint128::new(1));
+            assert_eq!(count(&res, "no"), Uint128::new(2));
+        }
+
+        #[test]
+        fn cycles_are_rejected() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            delegate(&mut app, &vote_addr, OWNER, None, VOTER1);
+            delegate(&mut app, &vote_addr, VOTER1, Some(1), VOTER2);
+
+            let err = try_delegate(&mut app, &vote_addr, VOTER2, Some(1), OWNER).unwrap_err();
+            assert_eq!(ContractError::DelegationCycle {}, err);
+            let err = try_delegate(&mut app, &vote_addr, VOTER1, None, VOTER1).unwrap_err();
+            assert_eq!(ContractError::DelegationCycle {}, err);
+            // globally voter1 does not delegate to voter2, but on the votebox it does
+            let err = try_delegate(&mut app, &vote_addr, VOTER2, None, OWNER).unwrap_err();
+            assert_eq!(ContractError::DelegationCycle {}, err);
+            delegate(&mut app, &vote_addr, VOTER2, Some(1), STRANGER);
+            delegate(&mut app, &vote_addr, VOTER2, None, OWNER);
+        }
+
+        #[test]
+        fn votebox_delegation_takes_precedence() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            delegate(&mut app, &vote_addr, VOTER1, None, VOTER2);
+            delegate(&mut app, &vote_addr, VOTER1, Some(1), OWNER);
+            assert_eq!(delegators(&app, &vote_addr, None, VOTER2), [VOTER1]);
+            assert_eq!(delegators(&app, &vote_addr, Some(1), OWNER), [VOTER1]);
+
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            vote(&mut app, &vote_addr, OWNER, 0);
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(count(&res, "yes"), Uint128::new(1));
+            assert_eq!(count(&res, "no"), Uint128::new(2));
+        }
+
+        #[test]
+        fn undelegate_removes_the_delegation() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            delegate(&mut app, &vote_addr, VOTER1, None, VOTER2);
+
+            let msg = ExecuteMsg::undelegate { id: None };
+            app.execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &msg, &[])
+                .unwrap();
+            assert!(delegators(&app, &vote_addr, None, VOTER2).is_empty());
+            let err = app
+                .execute_contract(Addr::unchecked(VOTER1), vote_addr.clone(), &msg, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::NotDelegated {}, err.downcast().unwrap());
+
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            assert_eq!(
+                count(&query_box(&app, &vote_addr, 1), "yes"),
+                Uint128::new(1)
+            );
+        }
+
+        #[test]
+        fn retracting_frees_the_delegated_weight() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            delegate(&mut app, &vote_addr, VOTER1, None, VOTER2);
+            vote(&mut app, &vote_addr, VOTER2, 2);
+
+            let msg = ExecuteMsg::retract_vote { id: Uint64::new(1) };
+            app.execute_contract(Addr::unchecked(VOTER2), vote_addr.clone(), &msg, &[])
+                .unwrap();
+            assert_eq!(
+                count(&query_box(&app, &vote_addr, 1), "yes"),
+                Uint128::zero()
+            );
+
+            vote(&mut app, &vote_addr, VOTER2, 0);
+            assert_eq!(
+                count(&query_box(&app, &vote_addr, 1), "no"),
+                Uint128::new(2)
+            );
+        }
+
+        fn undelegate(app: &mut App, vote_addr: &Addr, delegator: &str) {
+            let msg = ExecuteMsg::undelegate { id: None };
+            app.execute_contract(Addr::unchecked(delegator), vote_addr.clone(), &msg, &[])
+                .unwrap();
+        }
+
+        #[test]
+        fn long_delegation_chains_fail_the_vote() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            // delegator0 -> delegator1 -> ... -> delegator9 -> voter2
+            for i in 0..10 {
+                let delegate_to = match i {
+                    9 => VOTER2.to_string(),
+                    _ => format!("delegator{}", i + 1),
+                };
+                delegate(
+                    &mut app,
+                    &vote_addr,
+                    &format!("delegator{}", i),
+                    None,
+                    &delegate_to,
+                );
+            }
+
+            let err = try_vote(&mut app, &vote_addr, VOTER2, 2).unwrap_err();
+            assert_eq!(ContractError::DelegationTooDeep { max: 8 }, err);
+            undelegate(&mut app, &vote_addr, "delegator0");
+            undelegate(&mut app, &vote_addr, "delegator1");
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            assert_eq!(
+                count(&query_box(&app, &vote_addr, 1), "yes"),
+                Uint128::new(9)
+            );
+        }
+
+        #[test]
+        fn too_many_delegators_fail_the_vote() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            for i in 0..60 {
+                delegate(
+                    &mut app,
+                    &vote_addr,
+                    &format!("delegator{}", i),
+                    None,
+                    VOTER2,
+                );
+            }
+
+            let err = try_vote(&mut app, &vote_addr, VOTER2, 2).unwrap_err();
+            assert_eq!(ContractError::TooManyDelegators { max: 50 }, err);
+            for i in 50..60 {
+                undelegate(&mut app, &vote_addr, &format!("delegator{}", i));
+            }
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            assert_eq!(
+                count(&query_box(&app, &vote_addr, 1), "yes"),
+                Uint128::new(51)
+            );
+        }
+    }
+
+    mod lifecycle {
+        use super::*;
+        use crate::msg::{DelegatorsResponse, VoteBoxListResponse, VoteboxStatistics};
+
+        fn create_draft(app: &mut App, vote_addr: &Addr) {
+            let config = BoxConfig {
+                native_denom: Some(NATIVE_DENOM.to_string()),
+                draft: Some(true),
+                ..BoxConfig::default()
+            };
+            try_create_box(app, vote_addr, "draft", config).unwrap();
+        }
+
+        #[test]
+        fn drafts_only_accept_ballots_once_opened() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_draft(&mut app, &vote_addr);
+            assert_eq!(query_box(&app, &vote_addr, 1).status, Status::Draft {});
+
+            let err = try_vote(&mut app, &vote_addr, VOTER1, 2).unwrap_err();
+            let draft = ContractError::InvalidStatus {
+                status: Status::Draft {},
+            };
+            assert_eq!(draft, err);
+            // rewards can be set up before opening
+            app.execute_contract(
+                Addr::unchecked(OWNER),
+                vote_addr.clone(),
+                &ExecuteMsg::deposit { id: Uint64::new(1) },
+                &coins(100, NATIVE_DENOM),
+            )
+            .unwrap();
+
+            let open = ExecuteMsg::vote_open { id: Uint64::new(1) };
+            let err = try_execute(&mut app, &vote_addr, VOTER1, open.clone()).unwrap_err();
+            assert_eq!(ContractError::Unauthorized {}, err);
+            try_execute(&mut app, &vote_addr, OWNER, open.clone()).unwrap();
+            let err = try_execute(&mut app, &vote_addr, OWNER, open).unwrap_err();
+            let already_open = ContractError::InvalidStatus {
+                status: Status::Open {},
+            };
+            assert_eq!(already_open, err);
+
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            assert_eq!(query_box(&app, &vote_addr, 1).status, Status::Open {});
+        }
+
+        #[test]
+        fn open_voteboxes_close_at_the_deadline() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            create_draft(&mut app, &vote_addr);
+
+            end_voting(&mut app);
+            let res: VoteBoxListResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    &vote_addr,
+                    &QueryMsg::get_list {
+                        start_after: None,
+                        limit: None,
+                    },
+                )
+                .unwrap();
+            let statuses: Vec<_> = res.voteList.into_iter().map(|vb| vb.status).collect();
+            assert_eq!(statuses, [Status::Closed {}, Status::Draft {}]);
+
+            let err = try_vote(&mut app, &vote_addr, VOTER1, 2).unwrap_err();
+            assert_eq!(ContractError::Expired {}, err);
+        }
+
+        #[test]
+        fn cancelling_refunds_the_rewards() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", Some(NATIVE_DENOM));
+            app.execute_contract(
+                Addr::unchecked(OWNER),
+                vote_addr.clone(),
+                &ExecuteMsg::deposit { id: Uint64::new(1) },
+                &coins(100, NATIVE_DENOM),
+            )
+            .unwrap();
+            vote(&mut app, &vote_addr, VOTER1, 2);
+
+            let cancel = ExecuteMsg::vote_cancel { id: Uint64::new(1) };
+            try_execute(&mut app, &vote_addr, OWNER, cancel.clone()).unwrap();
+            assert_eq!(balance(&app, OWNER), Uint128::new(1_000));
+            assert_eq!(query_box(&app, &vote_addr, 1).status, Status::Cancelled {});
+
+            let cancelled = ContractError::InvalidStatus {
+                status: Status::Cancelled {},
+            };
+            let err = try_vote(&mut app, &vote_addr, VOTER2, 2).unwrap_err();
+            assert_eq!(cancelled, err);
+            let err = try_execute(&mut app, &vote_addr, OWNER, cancel).unwrap_err();
+            assert_eq!(cancelled, err);
+
+            end_voting(&mut app);
+            let claim = ExecuteMsg::claim { id: Uint64::new(1) };
+            let err = try_execute(&mut app, &vote_addr, VOTER1, claim).unwrap_err();
+            assert_eq!(cancelled, err);
+            assert!(query_result(&app, &vote_addr).is_err());
+        }
+
+        #[test]
+        fn statistics_leave_out_drafts_and_cancelled_voteboxes() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+            create_draft(&mut app, &vote_addr);
+            create_box(&mut app, &vote_addr, "cancelled", None);
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            let cancel = ExecuteMsg::vote_cancel { id: Uint64::new(3) };
+            try_execute(&mut app, &vote_addr, OWNER, cancel).unwrap();
+
+            let stats: VoteboxStatistics = app
+                .wrap()
+                .query_wasm_smart(&vote_addr, &QueryMsg::get_statistics {})
+                .unwrap();
+            assert_eq!(stats.total_voteboxes, Uint128::new(3));
+            assert_eq!(stats.active, Uint128::new(1));
+            assert_eq!(stats.expired, Uint128::zero());
+
+            end_voting(&mut app);
+            let stats: VoteboxStatistics = app
+                .wrap()
+                .query_wasm_smart(&vote_addr, &QueryMsg::get_statistics {})
+                .unwrap();
+            assert_eq!(stats.active, Uint128::zero());
+            assert_eq!(stats.expired, Uint128::new(1));
+            assert_eq!(stats.yes_won, Uint128::new(1));
+            assert_eq!(stats.ties, Uint128::zero());
+        }
+
+        #[test]
+        fn removing_refunds_the_rewards_and_clears_voters() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", Some(NATIVE_DENOM));
+            create_box(&mut app, &vote_addr, "cancelled", Some(NATIVE_DENOM));
+            for id in [1, 2] {
+                app.execute_contract(
+                    Addr::unchecked(OWNER),
+                    vote_addr.clone(),
+                    &ExecuteMsg::deposit {
+                        id: Uint64::new(id),
+                    },
+                    &coins(100, NATIVE_DENOM),
+                )
+                .unwrap();
+            }
+            let delegate = ExecuteMsg::delegate {
+                id: Some(Uint64::new(1)),
+                delegate_to: VOTER1.to_string(),
+            };
+            try_execute(&mut app, &vote_addr, VOTER2, delegate).unwrap();
+            vote(&mut app, &vote_addr, VOTER1, 2);
+
+            let remove = ExecuteMsg::vote_remove { id: Uint64::new(1) };
+            try_execute(&mut app, &vote_addr, OWNER, remove).unwrap();
+            assert_eq!(balance(&app, OWNER), Uint128::new(900));
+            let msg = QueryMsg::list_delegators {
+                id: Some(Uint64::new(1)),
+                delegate: VOTER1.to_string(),
+                start_after: None,
+                limit: None,
+            };
+            let res: DelegatorsResponse = app.wrap().query_wasm_smart(&vote_addr, &msg).unwrap();
+            assert!(res.delegators.is_empty());
+
+            // a cancelled votebox was refunded already
+            let cancel = ExecuteMsg::vote_cancel { id: Uint64::new(2) };
+            try_execute(&mut app, &vote_addr, OWNER, cancel).unwrap();
+            let remove = ExecuteMsg::vote_remove { id: Uint64::new(2) };
+            try_execute(&mut app, &vote_addr, OWNER, remove).unwrap();
+            assert_eq!(balance(&app, OWNER), Uint128::new(1_000));
+            assert_eq!(balance(&app, vote_addr.as_str()), Uint128::zero());
+        }
+
+        #[test]
+        fn closed_voteboxes_cannot_be_cancelled() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", None);
+
+            end_voting(&mut app);
+            let cancel = ExecuteMsg::vote_cancel { id: Uint64::new(1) };
+            let err = try_execute(&mut app, &vote_addr, OWNER, cancel).unwrap_err();
+            assert_eq!(ContractError::Expired {}, err);
+        }
+    }
+
+    mod finalize {
+        use super::*;
+
+        #[test]
+        fn anyone_can_record_the_result() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", Some(NATIVE_DENOM));
+            app.execute_contract(
+                Addr::unchecked(OWNER),
+                vote_addr.clone(),
+                &ExecuteMsg::deposit { id: Uint64::new(1) },
+                &coins(100, NATIVE_DENOM),
+            )
+            .unwrap();
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 2);
+            vote(&mut app, &vote_addr, OWNER, 0);
+
+            let err = try_finalize(&mut app, &vote_addr).unwrap_err();
+            assert_eq!(ContractError::Unexpired {}, err);
+
+            end_voting(&mut app);
+            let res = try_finalize(&mut app, &vote_addr).unwrap();
+            let event = res
+                .events
+                .iter()
+                .find(|event| event.ty == "wasm-votebox_finalized")
+                .unwrap();
+            assert!(event
+                .attributes
+                .iter()
+                .any(|attr| attr.key == "outcome" && attr.value == r#"{"winner":{"option":2}}"#));
+
+            let res = query_box(&app, &vote_addr, 1);
+            assert_eq!(res.status, Status::Finalized {});
+            let result = res.final_result.unwrap();
+            assert_eq!(result.outcome, Outcome::Winner { option: 2 });
+            assert_eq!(result.totals, [1, 0, 2, 0].map(Uint128::new));
+            assert_eq!(result.voter_count, Uint128::new(3));
+            assert_eq!(result.height, DEADLINE + 1);
+
+            let finalized = ContractError::InvalidStatus {
+                status: Status::Finalized {},
+            };
+            let err = try_finalize(&mut app, &vote_addr).unwrap_err();
+            assert_eq!(finalized, err);
+            let err = app
+                .execute_contract(
+                    Addr::unchecked(OWNER),
+                    vote_addr.clone(),
+                    &ExecuteMsg::vote_reset { id: Uint64::new(1) },
+                    &[],
+                )
+                .unwrap_err();
+            assert_eq!(finalized, err.downcast().unwrap());
+
+            // rewards can still be claimed
+            app.execute_contract(
+                Addr::unchecked(VOTER1),
+                vote_addr.clone(),
+                &ExecuteMsg::claim { id: Uint64::new(1) },
+                &[],
+            )
+            .unwrap();
+            assert_eq!(balance(&app, VOTER1), Uint128::new(33));
+        }
+
+        #[test]
+        fn finalized_outcome_no_longer_changes() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                tie_break: Some(TieBreak::OwnerCastingVote {}),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "tied", config).unwrap();
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 0);
+
+            end_voting(&mut app);
+            let err = try_finalize(&mut app, &vote_addr).unwrap_err();
+            assert_eq!(ContractError::AwaitingCastingVote {}, err);
+
+            let cast = |option| ExecuteMsg::cast_deciding_vote {
+                id: Uint64::new(1),
+                option,
+            };
+            app.execute_contract(Addr::unchecked(OWNER), vote_addr.clone(), &cast(2), &[])
+                .unwrap();
+            try_finalize(&mut app, &vote_addr).unwrap();
+            let err = app
+                .execute_contract(Addr::unchecked(OWNER), vote_addr.clone(), &cast(0), &[])
+                .unwrap_err();
+            let finalized = ContractError::InvalidStatus {
+                status: Status::Finalized {},
+            };
+            assert_eq!(finalized, err.downcast().unwrap());
+
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(res.outcome, Outcome::Winner { option: 2 });
+            assert_eq!(res.decided_by, Some(TieBreak::OwnerCastingVote {}));
+        }
+
+        #[test]
+        fn tie_falls_back_to_the_status_quo_when_the_owner_does_not_decide() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                tie_break: Some(TieBreak::OwnerCastingVote {}),
+                ..BoxConfig::default()
+            };
+            try_create_box(&mut app, &vote_addr, "tied", config).unwrap();
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 0);
+
+            app.update_block(|block| block.height = DEADLINE + 14_400);
+            let cast = ExecuteMsg::cast_deciding_vote {
+                id: Uint64::new(1),
+                option: 2,
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(OWNER), vote_addr.clone(), &cast, &[])
+                .unwrap_err();
+            assert_eq!(ContractError::CastingVoteClosed {}, err.downcast().unwrap());
+            try_finalize(&mut app, &vote_addr).unwrap();
+
+            let res = query_result(&app, &vote_addr).unwrap();
+            assert_eq!(
+                res.outcome,
+                Outcome::Tie {
+                    options: vec![0, 2]
+                }
+            );
+            assert_eq!(res.decided_by, Some(TieBreak::StatusQuo {}));
+        }
+    }
+
+    mod proposals {
+        use super::*;
+        use cosmwasm_std::{to_binary, BankMsg, WasmMsg};
+
+        fn create_proposal(app: &mut App, vote_addr: &Addr, msgs: Vec<CosmosMsg>) {
+            let config = BoxConfig {
+                allowlist: Some(vec![
+                    VOTER1.to_string(),
+                    VOTER2.to_string(),
+                    OWNER.to_string(),
+                ]),
+                thresholds: Some(gov_thresholds()),
+                msgs: Some(msgs),
+                ..BoxConfig::default()
+            };
+            try_create_box_as(app, vote_addr, ADMIN, "proposal", config).unwrap();
+        }
+
+        fn try_execute_proposal(
+            app: &mut App,
+            vote_addr: &Addr,
+        ) -> Result<AppResponse, ContractError> {
+            let msg = ExecuteMsg::execute_proposal { id: Uint64::new(1) };
+            app.execute_contract(Addr::unchecked(STRANGER), vote_addr.clone(), &msg, &[])
+                .map_err(|err| err.downcast().unwrap())
+        }
+
+        #[test]
+        fn passed_proposal_dispatches_its_messages() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let token = instantiate_cw20(&mut app, &[(vote_addr.as_str(), 500)]);
+            app.send_tokens(
+                Addr::unchecked(OWNER),
+                vote_addr.clone(),
+                &coins(100, NATIVE_DENOM),
+            )
+            .unwrap();
+            let msgs = vec![
+                BankMsg::Send {
+                    to_address: STRANGER.to_string(),
+                    amount: coins(100, NATIVE_DENOM),
+                }
+                .into(),
+                WasmMsg::Execute {
+                    contract_addr: token.to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: STRANGER.to_string(),
+                        amount: Uint128::new(50),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                }
+                .into(),
+            ];
+            create_proposal(&mut app, &vote_addr, msgs);
+            vote(&mut app, &vote_addr, VOTER1, 2);
+            vote(&mut app, &vote_addr, VOTER2, 2);
+
+            let err = try_execute_proposal(&mut app, &vote_addr).unwrap_err();
+            let open = ContractError::InvalidStatus {
+                status: Status::Open {},
+            };
+            assert_eq!(open, err);
+            end_voting(&mut app);
+            let err = try_execute_proposal(&mut app, &vote_addr).unwrap_err();
+            let closed = ContractError::InvalidStatus {
+                status: Status::Closed {},
+            };
+            assert_eq!(closed, err);
+
+            try_finalize(&mut app, &vote_addr).unwrap();
+            try_execute_proposal(&mut app, &vote_addr).unwrap();
+            assert_eq!(balance(&app, STRANGER), Uint128::new(100));
+            assert_eq!(cw20_balance(&app, &token, STRANGER), Uint128::new(50));
+            assert_eq!(query_box(&app, &vote_addr, 1).status, Status::Executed {});
+
+            let err = try_execute_proposal(&mut app, &vote_addr).unwrap_err();
+            let executed = ContractError::InvalidStatus {
+                status: Status::Executed {},
+            };
+            assert_eq!(executed, err);
+        }
+
+        #[test]
+        fn rejected_proposal_is_not_executed() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let msgs = vec![BankMsg::Send {
+                to_address: STRANGER.to_string(),
+                amount: coins(100, NATIVE_DENOM),
+            }
+            .into()];
+            create_proposal(&mut app, &vote_addr, msgs);
+            vote(&mut app, &vote_addr, VOTER1, 0);
+            vote(&mut app, &vote_addr, VOTER2, 0);
+
+            end_voting(&mut app);
+            try_finalize(&mut app, &vote_addr).unwrap();
+            let err = try_execute_proposal(&mut app, &vote_addr).unwrap_err();
+            assert_eq!(ContractError::NotPassed {}, err);
+        }
+
+        #[test]
+        fn messages_need_thresholds() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                msgs: Some(vec![BankMsg::Burn {
+                    amount: coins(1, NATIVE_DENOM),
+                }
+                .into()]),
+                ..BoxConfig::default()
+            };
+            let err = try_create_box_as(&mut app, &vote_addr, ADMIN, "poll", config).unwrap_err();
+            assert_eq!(ContractError::MessagesNeedThresholds {}, err);
+        }
+
+        #[test]
+        fn second_votebox_cannot_take_another_deposit() {
+            let (mut app, vote_addr) = proper_instantiate();
+            create_box(&mut app, &vote_addr, "poll", Some(NATIVE_DENOM));
+            app.execute_contract(
+                Addr::unchecked(OWNER),
+                vote_addr.clone(),
+                &ExecuteMsg::deposit { id: Uint64::new(1) },
+                &coins(100, NATIVE_DENOM),
+            )
+            .unwrap();
+
+            // a self-declared total weight of 1 would let a single yes vote pass
+            let drain = || BoxConfig {
+                voting_power: Some(VotingPower::Equal {}),
+                allowlist: Some(vec![STRANGER.to_string()]),
+                thresholds: Some(gov_thresholds()),
+                total_weight: Some(Uint128::new(1)),
+                msgs: Some(vec![BankMsg::Send {
+                    to_address: STRANGER.to_string(),
+                    amount: coins(100, NATIVE_DENOM),
+                }
+                .into()]),
+                ..BoxConfig::default()
+            };
+            for sender in [OWNER, STRANGER] {
+                let err =
+                    try_create_box_as(&mut app, &vote_addr, sender, "drain", drain()).unwrap_err();
+                assert_eq!(ContractError::Unauthorized {}, err);
+            }
+            let err = try_create_box_as(&mut app, &vote_addr, ADMIN, "drain", drain()).unwrap_err();
+            assert_eq!(ContractError::FixedTotalWeight {}, err);
+
+            assert_eq!(balance(&app, vote_addr.as_str()), Uint128::new(100));
+            assert_eq!(
+                query_box(&app, &vote_addr, 1).total_amount,
+                Uint128::new(100)
+            );
+        }
+
+        #[test]
+        fn allowlist_is_fixed_once_open() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let msgs = vec![BankMsg::Burn {
+                amount: coins(1, NATIVE_DENOM),
+            }
+            .into()];
+            create_proposal(&mut app, &vote_addr, msgs);
+            let update = ExecuteMsg::update_eligible_voters {
+                id: Uint64::new(1),
+                add: vec![STRANGER.to_string()],
+                remove: vec![VOTER1.to_string(), VOTER2.to_string()],
+            };
+            let err = app
+                .execute_contract(Addr::unchecked(OWNER), vote_addr.clone(), &update, &[])
+                .unwrap_err();
+            let open = ContractError::InvalidStatus {
+                status: Status::Open {},
+            };
+            assert_eq!(open, err.downcast().unwrap());
+        }
+
+        #[test]
+        fn token_voting_has_to_lock() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let token = instantiate_cw20(&mut app, &[(VOTER1, 100)]);
+            let config = |lock| BoxConfig {
+                voting_power: Some(VotingPower::Cw20Balance {
+                    token: token.clone(),
+                    lock,
+                }),
+                thresholds: Some(gov_thresholds()),
+                msgs: Some(vec![BankMsg::Burn {
+                    amount: coins(1, NATIVE_DENOM),
+                }
+                .into()]),
+                ..BoxConfig::default()
+            };
+            let err = try_create_box_as(&mut app, &vote_addr, ADMIN, "proposal", config(false))
+                .unwrap_err();
+            assert_eq!(ContractError::UnlockedVotingPower {}, err);
+            try_create_box_as(&mut app, &vote_addr, ADMIN, "proposal", config(true)).unwrap();
+        }
+    }
+
+    mod timelock {
+        use super::*;
+        use cosmwasm_std::BankMsg;
+
+        const GUARDIAN: &str = "guardian";
+
+        /// Passed and finalized proposal paying the stranger from the contract's funds
+        fn pass_proposal(app: &mut App, vote_addr: &Addr, timelock: Duration) {
+            app.send_tokens(
+                Addr::unchecked(OWNER),
+                vote_addr.clone(),
+                &coins(100, NATIVE_DENOM),
+            )
+            .unwrap();
+            let config = BoxConfig {
+                allowlist: Some(vec![VOTER1.to_string(), VOTER2.to_string()]),
+                thresholds: Some(gov_thresholds()),
+                msgs: Some(vec![BankMsg::Send {
+                    to_address: STRANGER.to_string(),
+                    amount: coins(100, NATIVE_DENOM),
+                }
+                .into()]),
+                timelock: Some(timelock),
+                guardian: Some(GUARDIAN.to_string()),
+                ..BoxConfig::default()
+            };
+            try_create_box_as(app, vote_addr, ADMIN, "treasury", config).unwrap();
+            vote(app, vote_addr, VOTER1, 2);
+            vote(app, vote_addr, VOTER2, 2);
+
+            end_voting(app);
+            try_finalize(app, vote_addr).unwrap();
+        }
+
+        #[test]
+        fn messages_wait_for_the_timelock() {
+            let (mut app, vote_addr) = proper_instantiate();
+            pass_proposal(&mut app, &vote_addr, Duration::Height(10));
+            let execute = ExecuteMsg::execute_proposal { id: Uint64::new(1) };
+
+            let err = try_execute(&mut app, &vote_addr, STRANGER, execute.clone()).unwrap_err();
+            assert_eq!(ContractError::Timelocked {}, err);
+
+            app.update_block(|block| block.height += 10);
+            try_execute(&mut app, &vote_addr, STRANGER, execute).unwrap();
+            assert_eq!(balance(&app, STRANGER), Uint128::new(100));
+        }
+
+        #[test]
+        fn guardian_vetoes_during_the_timelock() {
+            let (mut app, vote_addr) = proper_instantiate();
+            pass_proposal(&mut app, &vote_addr, Duration::Height(10));
+            let veto = ExecuteMsg::veto_execution { id: Uint64::new(1) };
+
+            let err = try_execute(&mut app, &vote_addr, OWNER, veto.clone()).unwrap_err();
+            assert_eq!(ContractError::Unauthorized {}, err);
+            try_execute(&mut app, &vote_addr, GUARDIAN, veto.clone()).unwrap();
+            let res = query_box(&app, &vote_addr, 1);
+            assert!(res.vetoed);
+            assert_eq!(res.status, Status::Cancelled {});
+            let err = try_execute(&mut app, &vote_addr, GUARDIAN, veto).unwrap_err();
+            assert_eq!(ContractError::Vetoed {}, err);
+            // claims get past the status check, this votebox just has no rewards
+            let claim = ExecuteMsg::claim { id: Uint64::new(1) };
+            let err = try_execute(&mut app, &vote_addr, VOTER1, claim).unwrap_err();
+            assert_eq!(ContractError::FreeVotes {}, err);
+
+            app.update_block(|block| block.height += 10);
+            let execute = ExecuteMsg::execute_proposal { id: Uint64::new(1) };
+            let err = try_execute(&mut app, &vote_addr, STRANGER, execute).unwrap_err();
+            assert_eq!(ContractError::Vetoed {}, err);
+            assert_eq!(balance(&app, STRANGER), Uint128::zero());
+        }
+
+        #[test]
+        fn veto_window_closes_with_the_timelock() {
+            let (mut app, vote_addr) = proper_instantiate();
+            pass_proposal(&mut app, &vote_addr, Duration::Time(3_600));
+
+            app.update_block(|block| block.time = block.time.plus_seconds(3_600));
+            let veto = ExecuteMsg::veto_execution { id: Uint64::new(1) };
+            let err = try_execute(&mut app, &vote_addr, GUARDIAN, veto).unwrap_err();
+            assert_eq!(ContractError::VetoClosed {}, err);
+
+            let execute = ExecuteMsg::execute_proposal { id: Uint64::new(1) };
+            try_execute(&mut app, &vote_addr, STRANGER, execute).unwrap();
+        }
+
+        #[test]
+        fn guardian_needs_a_timelock() {
+            let (mut app, vote_addr) = proper_instantiate();
+            let config = BoxConfig {
+                thresholds: Some(gov_thresholds()),
+                total_weight: Some(Uint128::new(10)),
+                guardian: Some(GUARDIAN.to_string()),
+                ..BoxConfig::default()
+            };
+            let err = try_create_box(&mut app, &vote_addr, "treasury", config).unwrap_err();
+            assert_eq!(ContractError::GuardianNeedsTimelock {}, err);
+        }
+    }
+}
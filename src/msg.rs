@@ -1,14 +1,28 @@
-use crate::state::Vote;
-use cosmwasm_std::{Uint128, Uint64};
-use cw_utils::Scheduled;
+use crate::state::{
+    Ballot, FinalResult, Outcome, Status, Thresholds, TieBreak, Vote, VoteKind, VoteOption,
+    VotingPower,
+};
+use cosmwasm_std::{Addr, CosmosMsg, Timestamp, Uint128, Uint64};
+use cw20::Cw20ReceiveMsg;
+use cw_utils::{Duration, Expiration, Scheduled};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct InstantiateMsg {}
+pub struct InstantiateMsg {
+    /// Only address allowed to create voteboxes with `msgs`. Without an admin no votebox
+    /// can carry messages.
+    pub admin: Option<String>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-#[allow(non_camel_case_types)]
+pub struct MigrateMsg {
+    /// Sets the admin of a contract instantiated before 0.2
+    pub admin: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[allow(non_camel_case_types, clippy::large_enum_variant)]
 pub enum ExecuteMsg {
     create_vote_box {
         deadline: Scheduled,
@@ -17,10 +31,103 @@ pub enum ExecuteMsg {
         description: String,
         create_date: String,
         native_denom: Option<String>,
+        cw20_addr: Option<String>,
+        voting_power: Option<VotingPower>,
+        allowlist: Option<Vec<String>>,
+        /// Custom option labels, defaults to no, abstain, yes and no_with_veto
+        options: Option<Vec<String>>,
+        kind: Option<VoteKind>,
+        /// Makes the votebox a secret ballot, with votes revealed between `deadline` and
+        /// this deadline
+        reveal_deadline: Option<Scheduled>,
+        /// Keeps running tallies out of queries until the deadline
+        hide_tally_until_end: Option<bool>,
+        /// Quorum and passing thresholds, only for single-choice voteboxes with the classic
+        /// options
+        thresholds: Option<Thresholds>,
+        /// Total weight of everyone eligible to vote. Required with `thresholds` unless it
+        /// can be read from the cw4 group, the cw20 supply or the allowlist.
+        total_weight: Option<Uint128>,
+        /// Defaults to `TieBreak::StatusQuo`
+        tie_break: Option<TieBreak>,
+        /// Creates the votebox as a draft that only accepts ballots once opened
+        draft: Option<bool>,
+        /// Messages to execute if the votebox passes, which needs `thresholds` and a total
+        /// weight read from the voting power source, cw20 voting power has to be locked.
+        /// They run as the contract, with access to everything it holds, so only the
+        /// contract admin may set them.
+        msgs: Option<Vec<CosmosMsg>>,
+        /// Delay between the votebox passing and its messages becoming executable
+        timelock: Option<Duration>,
+        /// Address that may veto the messages during the timelock
+        guardian: Option<String>,
     },
     vote {
         id: Uint64,
         vote_type: i32,
+        proof: Option<MerkleProof>,
+        memo: Option<String>,
+        /// Votes to add to the option on `VoteKind::Quadratic` voteboxes, defaults to 1
+        votes: Option<Uint128>,
+    },
+    /// Ballot for `VoteKind::RankedChoice` voteboxes, listing option indices from most to
+    /// least preferred. Options left out are never counted for this voter.
+    vote_ranked {
+        id: Uint64,
+        ranking: Vec<u32>,
+        proof: Option<MerkleProof>,
+        memo: Option<String>,
+    },
+    /// Ballot for `VoteKind::Approval` voteboxes, approving every listed option
+    vote_approval {
+        id: Uint64,
+        options: Vec<u32>,
+        proof: Option<MerkleProof>,
+        memo: Option<String>,
+    },
+    /// Hidden vote on a secret-ballot votebox, the hex encoded sha256 hash of
+    /// `"{id}:{voter}:{vote_type}:{salt}"`, binding it to this votebox and voter
+    commit_vote {
+        id: Uint64,
+        commitment: String,
+        proof: Option<MerkleProof>,
+        memo: Option<String>,
+    },
+    /// Reveals a committed vote once the voting deadline has passed
+    reveal_vote {
+        id: Uint64,
+        vote_type: i32,
+        salt: String,
+    },
+    change_vote {
+        id: Uint64,
+        vote_type: i32,
+    },
+    retract_vote {
+        id: Uint64,
+    },
+    /// Opens a draft votebox for voting
+    vote_open {
+        id: Uint64,
+    },
+    /// Withdraws a votebox before its deadline, refunding the deposited rewards
+    vote_cancel {
+        id: Uint64,
+    },
+    /// Records the result of a closed votebox for good. Anyone may call it, but a tie
+    /// waiting for the owner's casting vote is only recorded once the owner decided or
+    /// the window to do so is over, and then stays with the status quo.
+    finalize {
+        id: Uint64,
+    },
+    /// Dispatches the messages of a votebox finalized as passed. Anyone may call it.
+    execute_proposal {
+        id: Uint64,
+    },
+    /// Cancels a passed votebox so its messages never run, for the guardian during the
+    /// timelock. Voters can still claim their rewards.
+    veto_execution {
+        id: Uint64,
     },
     vote_reset {
         id: Uint64,
@@ -28,6 +135,59 @@ pub enum ExecuteMsg {
     vote_remove {
         id: Uint64,
     },
+    deposit {
+        id: Uint64,
+    },
+    claim {
+        id: Uint64,
+    },
+    receive(Cw20ReceiveMsg),
+    unlock {
+        id: Uint64,
+    },
+    /// Moves up to `limit` voters of a votebox created before 0.2 into the voter index.
+    /// Anyone may call it until the votebox has no pending voters left.
+    migrate_voters {
+        id: Uint64,
+        limit: Option<u32>,
+    },
+    /// Owner's pick among tied options on `TieBreak::OwnerCastingVote` voteboxes, within
+    /// 14_400 blocks or 86_400 seconds of the votes being counted
+    cast_deciding_vote {
+        id: Uint64,
+        option: u32,
+    },
+    /// Hands the sender's vote to `delegate_to` on votebox `id`, or on every votebox when
+    /// no id is given. A votebox delegation takes precedence over a global one. A vote
+    /// fails while chains over 8 delegations deep or more than 50 delegations lead to it.
+    delegate {
+        id: Option<Uint64>,
+        delegate_to: String,
+    },
+    undelegate {
+        id: Option<Uint64>,
+    },
+    /// Owner only. The allowlist of a votebox carrying messages is fixed once it opens.
+    update_eligible_voters {
+        id: Uint64,
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+}
+
+/// Eligibility proof for voteboxes using `VotingPower::Merkle`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MerkleProof {
+    pub weight: Uint128,
+    /// Hex encoded sibling hashes from the leaf up to the root
+    pub proof: Vec<String>,
+}
+
+/// Messages accepted through the cw20 `Send` hook
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[allow(non_camel_case_types)]
+pub enum ReceiveMsg {
+    deposit { id: Uint64 },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -49,8 +209,46 @@ pub enum QueryMsg {
         topic: String,
     },
     get_statistics {},
+    query_claim_status {
+        id: Uint64,
+        address: String,
+    },
+    list_eligible_voters {
+        id: Uint64,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    query_ballot {
+        id: Uint64,
+        voter: String,
+    },
+    list_ballots {
+        id: Uint64,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Addresses delegating directly to `delegate` on votebox `id`, or globally when no id
+    /// is given
+    list_delegators {
+        id: Option<Uint64>,
+        delegate: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Voice credits of a voter on a `VoteKind::Quadratic` votebox
+    query_credits {
+        id: Uint64,
+        voter: String,
+    },
+    /// Result of a votebox whose deadline has passed
+    query_result {
+        id: Uint64,
+    },
 }
 
+/// Option totals and winners only count voteboxes using the classic options, and leave
+/// out tallies still hidden until the deadline. `ties` counts closed voteboxes of any
+/// kind where the top options tied.
 #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 pub struct VoteboxStatistics {
     pub total_participants: Uint128,
@@ -65,40 +263,71 @@ pub struct VoteboxStatistics {
     pub total_no_count: Uint128,
     pub total_abstain_count: Uint128,
     pub total_no_veto_count: Uint128,
+    pub ties: Uint128,
 }
 
 /// We define a custom struct for each query response
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct VoteResponse {
     pub id: Uint64,
-    pub yes_count: Uint128,
-    pub no_count: Uint128,
-    pub abstain_count: Uint128,
-    pub no_with_veto_count: Uint128,
+    pub options: Vec<VoteOption>,
+    pub kind: VoteKind,
+    pub status: Status,
     pub deadline: Scheduled,
     pub owner: String,
     pub topic: String,
     pub description: String,
     pub create_date: String,
     pub native_denom: Option<String>,
+    pub cw20_addr: Option<Addr>,
     pub total_amount: Uint128,
+    pub voter_count: Uint128,
+    pub voting_power: VotingPower,
+    pub allowlist: bool,
+    pub reveal_deadline: Option<Scheduled>,
+    pub hide_tally_until_end: bool,
+    pub thresholds: Option<Thresholds>,
+    pub total_weight: Option<Uint128>,
+    pub tie_break: TieBreak,
+    pub casting_vote: Option<u32>,
+    pub final_result: Option<FinalResult>,
+    pub msgs: Vec<CosmosMsg>,
+    pub timelock: Option<Duration>,
+    pub guardian: Option<Addr>,
+    pub executable_at: Option<Expiration>,
+    pub vetoed: bool,
 }
 
-impl Into<VoteResponse> for Vote {
-    fn into(self) -> VoteResponse {
+impl From<Vote> for VoteResponse {
+    fn from(vote: Vote) -> VoteResponse {
         VoteResponse {
-            id: self.id,
-            owner: self.owner,
-            yes_count: self.yes_count,
-            no_count: self.no_count,
-            abstain_count: self.abstain_count,
-            no_with_veto_count: self.no_with_veto_count,
-            deadline: self.deadline,
-            topic: self.topic,
-            description: self.description,
-            create_date: self.create_date,
-            native_denom: self.native_denom,
-            total_amount: self.total_amount,
+            id: vote.id,
+            owner: vote.owner,
+            options: vote.options,
+            kind: vote.kind,
+            status: vote.status,
+            deadline: vote.deadline,
+            topic: vote.topic,
+            description: vote.description,
+            create_date: vote.create_date,
+            native_denom: vote.native_denom,
+            cw20_addr: vote.cw20_addr,
+            total_amount: vote.total_amount,
+            voter_count: vote.voter_count,
+            voting_power: vote.voting_power,
+            allowlist: vote.allowlist,
+            reveal_deadline: vote.reveal_deadline,
+            hide_tally_until_end: vote.hide_tally_until_end,
+            thresholds: vote.thresholds,
+            total_weight: vote.total_weight,
+            tie_break: vote.tie_break,
+            casting_vote: vote.casting_vote,
+            final_result: vote.final_result,
+            msgs: vote.msgs,
+            timelock: vote.timelock,
+            guardian: vote.guardian,
+            executable_at: vote.executable_at,
+            vetoed: vote.vetoed,
         }
     }
 }
@@ -115,6 +344,92 @@ pub struct VoteBoxListResponse {
     pub voteList: Vec<VoteResponse>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimStatusResponse {
+    pub voted: bool,
+    pub claimed: bool,
+    /// Reward paid to (or still claimable by) the address
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EligibleVotersResponse {
+    pub voters: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DelegatorsResponse {
+    pub delegators: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BallotResponse {
+    pub voter: String,
+    pub vote_type: i32,
+    pub ranking: Vec<u32>,
+    pub approvals: Vec<u32>,
+    pub allocation: Vec<Uint128>,
+    pub weight: Uint128,
+    pub height: u64,
+    pub time: Timestamp,
+    pub memo: Option<String>,
+}
+
+impl BallotResponse {
+    pub fn new(voter: Addr, ballot: Ballot) -> Self {
+        BallotResponse {
+            voter: voter.into(),
+            vote_type: ballot.vote_type,
+            ranking: ballot.ranking,
+            approvals: ballot.approvals,
+            allocation: ballot.allocation,
+            weight: ballot.weight,
+            height: ballot.height,
+            time: ballot.time,
+            memo: ballot.memo,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BallotListResponse {
+    pub ballots: Vec<BallotResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CreditsResponse {
+    pub budget: Uint128,
+    pub spent: Uint128,
+    pub remaining: Uint128,
+}
+
+/// Option tallies of one counting round. Ranked-choice results have one round per
+/// elimination, all other voteboxes a single round.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RunoffRound {
+    /// Weight counted for each option, zero for options eliminated in earlier rounds
+    pub tallies: Vec<Uint128>,
+    /// Weight of ballots with no continuing option left
+    pub exhausted: Uint128,
+    /// Options eliminated at the end of this round
+    pub eliminated: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ResultResponse {
+    pub id: Uint64,
+    pub rounds: Vec<RunoffRound>,
+    /// Winner of the count, or the result under the passing thresholds of voteboxes
+    /// that have them
+    pub outcome: Outcome,
+    /// Tie-break policy that settled a tie between the top options
+    pub decided_by: Option<TieBreak>,
+    /// Commitments on a secret-ballot votebox that were not revealed (yet) and are left
+    /// out of the tallies
+    pub unrevealed: u64,
+    pub unrevealed_weight: Uint128,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct VBOCResponse {
     pub open: Uint64,
@@ -1,17 +1,22 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Uint128, Uint64};
+use cosmwasm_std::{Addr, CosmosMsg, Decimal, Empty, Timestamp, Uint128, Uint64};
 use cw_storage_plus::{Item, Map};
-use cw_utils::Scheduled;
+use cw_utils::{Duration, Expiration, Scheduled};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Vote {
     pub id: Uint64,
-    pub yes_count: Uint128,
-    pub no_count: Uint128,
-    pub abstain_count: Uint128,
-    pub no_with_veto_count: Uint128,
+    /// Labelled options with their tallies, indexed by `vote_type`
+    #[serde(default)]
+    pub options: Vec<VoteOption>,
+    #[serde(default)]
+    pub kind: VoteKind,
+    /// Stage of the votebox as last stored. An open votebox whose deadline has passed is
+    /// closed, see `helpers::current_status`.
+    #[serde(default)]
+    pub status: Status,
     pub deadline: Scheduled,
     pub owner: String,
     pub topic: String,
@@ -19,9 +24,274 @@ pub struct Vote {
     pub create_date: String,
     pub total_amount: Uint128,
     pub native_denom: Option<String>,
-    pub voters: Vec<Addr>,
+    pub cw20_addr: Option<Addr>,
     pub voter_count: Uint128,
+    #[serde(default)]
+    pub voting_power: VotingPower,
+    /// Only addresses in `ELIGIBLE_VOTERS` may vote
+    #[serde(default)]
+    pub allowlist: bool,
+    /// Set on secret-ballot voteboxes. Voters commit to a hidden vote until `deadline`,
+    /// then reveal it until this deadline; only revealed votes are tallied.
+    pub reveal_deadline: Option<Scheduled>,
+    /// Queries report zero tallies until the deadline has passed
+    #[serde(default)]
+    pub hide_tally_until_end: bool,
+    /// Passing rules deciding the outcome once the deadline has passed
+    pub thresholds: Option<Thresholds>,
+    /// Total weight of everyone eligible to vote, as given by the creator. Otherwise it is
+    /// taken from the cw4 group, the cw20 supply or the allowlist when the result is read.
+    pub total_weight: Option<Uint128>,
+    /// How a tie between the top options is decided
+    #[serde(default)]
+    pub tie_break: TieBreak,
+    /// Option picked by the owner to decide a tie under `TieBreak::OwnerCastingVote`
+    pub casting_vote: Option<u32>,
+    /// Number of tally changes so far, used to order them
+    #[serde(default)]
+    pub tally_updates: u64,
+    /// Height of the last tally change, seeding `TieBreak::Random`
+    #[serde(default)]
+    pub last_tally_height: u64,
+    /// Share of `total_amount` paid to each voter, fixed by the first claim after the deadline
+    pub payout: Option<Uint128>,
+    /// Set once the votebox is finalized
+    pub final_result: Option<FinalResult>,
+    /// Messages the contract dispatches once the votebox is finalized as passed
+    #[serde(default)]
+    pub msgs: Vec<CosmosMsg>,
+    /// Delay between passing and the messages becoming executable
+    pub timelock: Option<Duration>,
+    /// Address that may veto the messages until the timelock ends
+    pub guardian: Option<Addr>,
+    /// End of the timelock, set when the votebox is finalized as passed
+    pub executable_at: Option<Expiration>,
+    /// Set when the guardian has vetoed the messages
+    #[serde(default)]
+    pub vetoed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VoteOption {
+    pub label: String,
+    pub count: Uint128,
+    /// `tally_updates` of the votebox when this tally last changed
+    #[serde(default)]
+    pub reached_at: u64,
+}
+
+/// Labels of the options a votebox gets when no custom options are given. The index
+/// of each label is the `vote_type` that selects it.
+pub const CLASSIC_OPTIONS: [&str; 4] = ["no", "abstain", "yes", "no_with_veto"];
+
+/// Passing rules as in Cosmos SDK governance, for voteboxes with the classic options
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Thresholds {
+    /// Share of the eligible total weight that has to vote
+    pub quorum: Decimal,
+    /// Share of the non-abstaining weight that has to vote yes
+    pub threshold: Decimal,
+    /// Share of the voted weight voting no_with_veto that rejects the votebox outright
+    pub veto_threshold: Decimal,
+}
+
+/// Result of a closed votebox
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// Nobody voted
+    NoVotes {},
+    /// `option` received the most weight
+    Winner {
+        option: u32,
+    },
+    /// The listed options share the most weight
+    Tie {
+        options: Vec<u32>,
+    },
+    // outcomes of voteboxes with passing thresholds
+    Passed {},
+    Rejected {},
+    RejectedWithVeto {},
+    QuorumNotMet {},
+}
+
+/// Lifecycle stage of a votebox
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    /// Being set up by the owner and not accepting ballots yet
+    Draft {},
+    /// Accepting ballots until the deadline
+    Open {},
+    /// Deadline has passed and the result can be read
+    Closed {},
+    /// Result has been recorded for good
+    Finalized {},
+    /// Withdrawn by the owner before the deadline, or vetoed by the guardian
+    Cancelled {},
+    /// Proposal passed and has been carried out
+    Executed {},
+}
+
+impl Default for Status {
+    fn default() -> Self {
+        Status::Open {}
+    }
+}
+
+/// Result recorded when a votebox is finalized, so later changes to voting power or
+/// tie-break inputs no longer affect it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FinalResult {
+    pub outcome: Outcome,
+    /// Tie-break policy that decided the outcome, if a tie had to be broken
+    pub decided_by: Option<TieBreak>,
+    /// Option tallies, indexed by `vote_type`
+    pub totals: Vec<Uint128>,
+    pub voter_count: Uint128,
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+/// Policy deciding between options tied for the most weight
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TieBreak {
+    /// The tie stands and no option wins, keeping the status quo
+    StatusQuo {},
+    /// The tied option whose tally reached its final count first wins
+    EarliestToReach {},
+    /// The owner picks one of the tied options after the deadline
+    OwnerCastingVote {},
+    /// A tied option is picked pseudo-randomly, seeded by the block of the last tally
+    /// change so every query agrees on it
+    Random {},
+}
+
+impl Default for TieBreak {
+    fn default() -> Self {
+        TieBreak::StatusQuo {}
+    }
+}
+
+/// What a ballot looks like and how the result is computed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteKind {
+    /// Each voter picks one option, the option with most weight wins
+    Single {},
+    /// Each voter ranks options in order of preference and the winner is found by
+    /// instant-runoff elimination. Option tallies count first preferences.
+    RankedChoice {},
+    /// Each voter approves any number of options, each receiving the voter's full weight.
+    /// The most approved option wins.
+    Approval {},
+    /// Each voter gets `credits` voice credits to spread over the options across any
+    /// number of ballots, where n votes on one option cost n² credits. The voting power
+    /// rule only decides who is eligible.
+    Quadratic { credits: Uint128 },
+}
+
+impl Default for VoteKind {
+    fn default() -> Self {
+        VoteKind::Single {}
+    }
+}
+
+/// How much weight a single address adds to the option it votes for
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VotingPower {
+    /// One address, one vote
+    Equal {},
+    /// The voter's balance of `token` at vote time. With `lock` set the balance is
+    /// pulled into the contract (the voter must grant an allowance first) and can be
+    /// unlocked once the deadline has passed.
+    Cw20Balance { token: Addr, lock: bool },
+    /// The voter's total stake delegated to validators at vote time
+    Staked {},
+    /// Only members of the cw4 `group` may vote, each with its member weight
+    Cw4Group { group: Addr },
+    /// Voters prove an `address + weight` leaf of the sha256 merkle tree with this hex
+    /// encoded root, so large snapshots never have to be stored on chain
+    Merkle { root: String },
+}
+
+impl Default for VotingPower {
+    fn default() -> Self {
+        VotingPower::Equal {}
+    }
+}
+
+/// What a voter picked, how much weight it carried and when it was last cast
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Ballot {
+    /// Picked option, the first preference of a ranked ballot or the first option of an
+    /// approval ballot
+    pub vote_type: i32,
+    /// Options of a ranked ballot in order of preference
+    #[serde(default)]
+    pub ranking: Vec<u32>,
+    /// Options approved by an approval ballot
+    #[serde(default)]
+    pub approvals: Vec<u32>,
+    /// Votes bought for each option by a quadratic ballot
+    #[serde(default)]
+    pub allocation: Vec<Uint128>,
+    pub weight: Uint128,
+    pub height: u64,
+    pub time: Timestamp,
+    pub memo: Option<String>,
 }
 
 pub const VOTE_BOX_LIST: Map<u64, Vote> = Map::new("votebox list");
 pub const VOTE_BOX_SEQ: Item<Uint64> = Item::new("votebox seq");
+/// Only address allowed to create voteboxes that carry messages
+pub const ADMIN: Item<Addr> = Item::new("admin");
+/// Hidden vote of a voter on a secret-ballot votebox, waiting to be revealed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Commitment {
+    /// Hex encoded sha256 hash of `"{id}:{voter}:{vote_type}:{salt}"`
+    pub hash: String,
+    pub weight: Uint128,
+    pub height: u64,
+    pub time: Timestamp,
+    pub memo: Option<String>,
+}
+
+/// Weight of a delegator counted in the ballot of the delegate that voted for it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DelegatedVote {
+    pub delegate: Addr,
+    pub weight: Uint128,
+}
+
+/// Amount paid out to each voter that has claimed its reward, keyed by votebox id and voter
+pub const CLAIMS: Map<(u64, &Addr), Uint128> = Map::new("claims");
+/// cw20 tokens locked by token-weighted votes, keyed by votebox id and voter
+pub const LOCKED_TOKENS: Map<(u64, &Addr), Uint128> = Map::new("locked tokens");
+/// Addresses allowed to vote on allowlisted voteboxes, keyed by votebox id and voter
+pub const ELIGIBLE_VOTERS: Map<(u64, &Addr), Empty> = Map::new("eligible voters");
+/// Ballot of every voter, keyed by votebox id and voter
+pub const BALLOTS: Map<(u64, &Addr), Ballot> = Map::new("ballots");
+/// Unrevealed commitments on secret-ballot voteboxes, keyed by votebox id and voter
+pub const COMMITMENTS: Map<(u64, &Addr), Commitment> = Map::new("commitments");
+/// Addresses that voted, keyed by votebox id and voter
+pub const VOTERS: Map<(u64, &Addr), Empty> = Map::new("voters");
+/// Voters of a votebox created before 0.2 that still have to be moved into `VOTERS`, keyed
+/// by votebox id. The votebox takes no ballots or claims until the list is empty.
+pub const PENDING_VOTERS: Map<u64, Vec<Addr>> = Map::new("pending voters");
+/// Delegate each delegator hands its vote to, keyed by votebox id (`GLOBAL_DELEGATION` for
+/// every votebox) and delegator
+pub const DELEGATIONS: Map<(u64, &Addr), Addr> = Map::new("delegations");
+/// Reverse index of `DELEGATIONS`, keyed by votebox id, delegate and delegator
+pub const DELEGATORS: Map<(u64, &Addr, &Addr), Empty> = Map::new("delegators");
+/// Votebox ids each delegator has a votebox delegation on, keyed by delegator and votebox id
+pub const DELEGATED_BOXES: Map<(&Addr, u64), Empty> = Map::new("delegated boxes");
+/// Delegators counted in a delegate's ballot, keyed by votebox id and delegator
+pub const DELEGATED_VOTES: Map<(u64, &Addr), DelegatedVote> = Map::new("delegated votes");
+/// Reverse index of `DELEGATED_VOTES`, keyed by votebox id, delegate and delegator
+pub const COUNTED_DELEGATORS: Map<(u64, &Addr, &Addr), Empty> = Map::new("counted delegators");
+/// Votebox id under which delegations for every votebox are stored. Votebox ids start at 1.
+pub const GLOBAL_DELEGATION: u64 = 0;